@@ -0,0 +1,290 @@
+use crate::ui::UiMode;
+use crate::State;
+
+/// Something the user can do from the tracker pane, by key or by name in
+/// the `:` palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    SelectNext,
+    SelectPrevious,
+    Copy,
+    OpenCapture,
+    TogglePreview,
+    Mirror,
+    Rerun,
+    AcceptName,
+    Acknowledge,
+    AcknowledgeAll,
+    AlertsPane,
+    Mark,
+    Bulk,
+    ClearMarks,
+    BundleProfile,
+    Broadcast,
+    Search,
+    NextMatch,
+    ToggleHidden,
+    TogglePause,
+    Pause,
+    Resume,
+    NewPane,
+    SaveSet,
+    OpenSet,
+    CompareSnapshots,
+    Export,
+    Snapshot,
+    Palette,
+    Setup,
+    Help,
+}
+
+impl Action {
+    /// Every action, in the order the help overlay and palette list them
+    pub const ALL: [Action; 31] = [
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::Copy,
+        Action::OpenCapture,
+        Action::TogglePreview,
+        Action::Mirror,
+        Action::Rerun,
+        Action::AcceptName,
+        Action::Acknowledge,
+        Action::AcknowledgeAll,
+        Action::AlertsPane,
+        Action::Mark,
+        Action::Bulk,
+        Action::ClearMarks,
+        Action::BundleProfile,
+        Action::Broadcast,
+        Action::Search,
+        Action::NextMatch,
+        Action::ToggleHidden,
+        Action::TogglePause,
+        Action::Pause,
+        Action::Resume,
+        Action::NewPane,
+        Action::SaveSet,
+        Action::OpenSet,
+        Action::CompareSnapshots,
+        Action::Export,
+        Action::Snapshot,
+        Action::Palette,
+        Action::Setup,
+        Action::Help,
+    ];
+
+    /// Name typed in the palette
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::SelectNext => "next",
+            Action::SelectPrevious => "previous",
+            Action::Copy => "copy",
+            Action::OpenCapture => "open capture",
+            Action::TogglePreview => "preview",
+            Action::Mirror => "mirror",
+            Action::Rerun => "rerun",
+            Action::AcceptName => "accept name",
+            Action::Acknowledge => "acknowledge",
+            Action::AcknowledgeAll => "acknowledge all",
+            Action::AlertsPane => "alerts pane",
+            Action::Mark => "mark",
+            Action::Bulk => "bulk",
+            Action::ClearMarks => "clear marks",
+            Action::BundleProfile => "bundle profile",
+            Action::Broadcast => "broadcast",
+            Action::Search => "search",
+            Action::NextMatch => "next match",
+            Action::ToggleHidden => "toggle hidden",
+            Action::TogglePause => "toggle pause",
+            Action::Pause => "pause",
+            Action::Resume => "resume",
+            Action::NewPane => "new pane",
+            Action::SaveSet => "save set",
+            Action::OpenSet => "open set",
+            Action::CompareSnapshots => "compare snapshots",
+            Action::Export => "export",
+            Action::Snapshot => "snapshot",
+            Action::Palette => "palette",
+            Action::Setup => "setup",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::SelectNext => "select the next pane",
+            Action::SelectPrevious => "select the previous pane",
+            Action::Copy => "copy the selected pane's capture",
+            Action::OpenCapture => "open the selected pane's capture in an editor",
+            Action::TogglePreview => "toggle the floating preview of the selected pane",
+            Action::Mirror => "mirror the selected pane's capture in a new pane",
+            Action::Rerun => "re-run the selected pane's command",
+            Action::AcceptName => "accept the suggested name for the selected pane",
+            Action::Acknowledge => "acknowledge the selected pane's failure alert",
+            Action::AcknowledgeAll => "acknowledge every failure alert",
+            Action::AlertsPane => "open a floating pane following failures and watched commands",
+            Action::Mark => "mark or unmark the selected pane for a bulk action",
+            Action::Bulk => "capture, tag, group, bundle or close the marked panes",
+            Action::ClearMarks => "unmark every pane",
+            Action::BundleProfile => "bundle the panes of a context profile",
+            Action::Broadcast => "type a line into every pane of the selected pane's category",
+            Action::Search => "search pane names and captures",
+            Action::NextMatch => "jump to the next search match",
+            Action::ToggleHidden => "hide or show suppressed and unselectable panes",
+            Action::TogglePause => "pause or resume tracking",
+            Action::Pause => "pause tracking",
+            Action::Resume => "resume tracking",
+            Action::NewPane => "open a new pane from a template",
+            Action::SaveSet => "save the selected pane's tab as a named pane set",
+            Action::OpenSet => "re-create a saved pane set in the current tab",
+            Action::CompareSnapshots => "diff two snapshots of the selected pane (git_snapshots)",
+            Action::Export => "rewrite every export file now",
+            Action::Snapshot => "commit the export directory now (git_snapshots)",
+            Action::Palette => "run an action by name",
+            Action::Setup => "show permissions, file paths and disabled features",
+            Action::Help => "show or close the key list",
+        }
+    }
+}
+
+/// How well `query` matches `candidate` as a subsequence, lower is better.
+/// `None` when some character of the query doesn't appear in order.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut rest = candidate.char_indices().peekable();
+    let mut last = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_ascii_lowercase();
+        let (index, _) = rest.by_ref().find(|(_, c)| c.to_ascii_lowercase() == q)?;
+        // Gaps between matched characters and a late first match both count
+        score += match last {
+            Some(last) => index - last - 1,
+            None => index,
+        };
+        last = Some(index);
+    }
+    Some(score)
+}
+
+/// Actions matching `query`, best first
+pub fn palette_matches(query: &str) -> Vec<Action> {
+    let mut matches: Vec<(usize, Action)> = Action::ALL
+        .iter()
+        .filter_map(|action| fuzzy_score(query, action.name()).map(|score| (score, *action)))
+        .collect();
+    matches.sort_by_key(|(score, action)| (*score, action.name().len()));
+    matches.into_iter().map(|(_, action)| action).collect()
+}
+
+impl State {
+    /// Carry out `action` as if its key had been pressed
+    pub(crate) fn run_action(&mut self, action: Action) {
+        match action {
+            Action::SelectNext => self.move_selection(1),
+            Action::SelectPrevious => self.move_selection(-1),
+            Action::Copy => self.copy_selected(),
+            Action::OpenCapture => self.open_selected_in_editor(),
+            Action::TogglePreview => self.toggle_preview(),
+            Action::Mirror => self.mirror_selected(),
+            Action::Rerun => self.rerun_selected(),
+            Action::AcceptName => self.accept_suggested_name(),
+            Action::Acknowledge => self.acknowledge_selected(),
+            Action::AcknowledgeAll => {
+                let count = self.acknowledge_all();
+                self.status = Some(format!("Acknowledged {} alerts", count));
+            }
+            Action::AlertsPane => self.open_alerts_pane(),
+            Action::Mark => self.toggle_mark(),
+            Action::Bulk => {
+                if self.selected_pane_id().is_some() {
+                    self.mode = UiMode::Bulk;
+                }
+            }
+            Action::ClearMarks => self.marked.clear(),
+            Action::BundleProfile => {
+                if self.config.context_profiles.is_empty() {
+                    self.status = Some("No context_profiles configured".to_string());
+                } else {
+                    self.mode = UiMode::Profiles;
+                }
+            }
+            Action::Broadcast => self.start_broadcast(),
+            Action::Search => self.mode = UiMode::Search(String::new()),
+            Action::NextMatch => self.next_search_hit(),
+            Action::ToggleHidden => {
+                self.hide_unselectable = !self.hide_unselectable;
+                self.save_state();
+            }
+            Action::TogglePause => self.set_paused(!self.paused),
+            Action::Pause => self.set_paused(true),
+            Action::Resume => self.set_paused(false),
+            Action::NewPane => {
+                if self.config.pane_templates.is_empty() {
+                    self.status = Some("No pane_templates configured".to_string());
+                } else {
+                    self.mode = UiMode::Spawn;
+                }
+            }
+            Action::SaveSet => {
+                if self.selected_pane_id().is_some() {
+                    self.mode = UiMode::SaveSet(String::new());
+                }
+            }
+            Action::OpenSet => {
+                if self.pane_sets.is_empty() {
+                    self.status = Some("No pane sets saved yet (S saves one)".to_string());
+                } else {
+                    self.mode = UiMode::PaneSets;
+                }
+            }
+            Action::CompareSnapshots => self.compare_snapshots(),
+            Action::Export => {
+                self.export_digests.clear();
+                self.export_to_file();
+                self.status = Some("Exported".to_string());
+            }
+            Action::Snapshot => {
+                if self.config.git_snapshots {
+                    self.snapshot_commit();
+                    self.status = Some("Committing a snapshot".to_string());
+                } else {
+                    self.status = Some("git_snapshots is off".to_string());
+                }
+            }
+            Action::Palette => self.mode = UiMode::Palette(String::new()),
+            Action::Setup => self.onboarded = false,
+            Action::Help => self.mode = UiMode::Help,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score_counts_gaps_and_late_starts() {
+        assert_eq!(fuzzy_score("", "copy"), Some(0));
+        assert_eq!(fuzzy_score("cop", "copy"), Some(0));
+        assert_eq!(fuzzy_score("CpY", "copy"), Some(1));
+        assert_eq!(fuzzy_score("open cap", "open capture"), Some(1));
+        assert_eq!(fuzzy_score("py", "copy"), Some(2));
+        assert_eq!(fuzzy_score("yc", "copy"), None);
+        assert_eq!(fuzzy_score("copyx", "copy"), None);
+    }
+
+    #[test]
+    fn palette_ranks_tight_and_short_matches_first() {
+        assert_eq!(
+            palette_matches("ack"),
+            vec![Action::Acknowledge, Action::AcknowledgeAll]
+        );
+        assert_eq!(
+            palette_matches("snap"),
+            vec![Action::Snapshot, Action::CompareSnapshots]
+        );
+        assert_eq!(palette_matches("").len(), Action::ALL.len());
+        assert!(palette_matches("zzz").is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::commands::CommandKind;
+use crate::diff::ManifestDiff;
+use crate::focus;
+use crate::spawn::open_named_command_pane;
+use crate::{terminal_id, unix_now, State};
+use std::fmt::Write;
+use zellij_tile::prelude::*;
+
+/// Name of the floating pane following the alerts log
+const ALERTS_PANE: &str = "alerts";
+
+impl State {
+    /// Terminal ids of panes whose command failed, acknowledged or not
+    pub(crate) fn alerting(&self) -> impl Iterator<Item = u32> + '_ {
+        self.panes
+            .iter()
+            .filter(|(_, pane)| pane.failed())
+            .filter_map(|(id, _)| terminal_id(id))
+    }
+
+    /// Whether a failed pane still needs attention
+    pub(crate) fn is_unacknowledged(&self, id: u32) -> bool {
+        !self.acknowledged.contains(&id)
+    }
+
+    /// Acknowledge the alert of one failed terminal pane
+    pub(crate) fn acknowledge(&mut self, id: u32) -> Result<(), String> {
+        if !self.alerting().any(|alert| alert == id) {
+            return Err(format!("terminal_{} has no alert", id));
+        }
+        if self.acknowledged.insert(id) {
+            self.alerts_changed();
+        }
+        Ok(())
+    }
+
+    /// Acknowledge every current alert, returning how many were new
+    pub(crate) fn acknowledge_all(&mut self) -> usize {
+        let ids: Vec<u32> = self.alerting().collect();
+        let added = ids
+            .into_iter()
+            .filter(|id| self.acknowledged.insert(*id))
+            .count();
+        if added > 0 {
+            self.alerts_changed();
+        }
+        added
+    }
+
+    /// Acknowledge the selected pane's alert
+    pub(crate) fn acknowledge_selected(&mut self) {
+        let Some(id) = self.selected_pane_id().and_then(|id| terminal_id(id)) else {
+            return;
+        };
+        self.status = Some(match self.acknowledge(id) {
+            Ok(()) => format!("Acknowledged terminal_{}", id),
+            Err(e) => e,
+        });
+    }
+
+    /// Forget acknowledgements of panes that were closed or stopped failing,
+    /// so the next failure alerts again
+    pub(crate) fn prune_acknowledged(&mut self) {
+        let alerting: Vec<u32> = self.alerting().collect();
+        let before = self.acknowledged.len();
+        self.acknowledged.retain(|id| alerting.contains(id));
+        if self.acknowledged.len() != before {
+            self.save_state();
+        }
+    }
+
+    /// Append a line per pane that failed or started a watched command to
+    /// `zj-alerts.log`
+    pub(crate) fn log_alerts(&mut self, diff: &ManifestDiff) {
+        let time = focus::iso8601(unix_now());
+        let mut lines = String::new();
+        for pane_id in &diff.failed {
+            let Some(pane) = self.panes.get(pane_id) else {
+                continue;
+            };
+            let _ = writeln!(
+                lines,
+                "{} [failed] {} '{}' exited with status {}",
+                time,
+                self.config.pane_id_format.apply(pane_id),
+                pane.info.title,
+                pane.info
+                    .exit_status
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "?".to_string())
+            );
+        }
+        for (pane_id, watcher) in &diff.commands {
+            let Some(pane) = self.panes.get(pane_id) else {
+                continue;
+            };
+            let _ = writeln!(
+                lines,
+                "{} [{}] {} '{}': {}",
+                time,
+                watcher,
+                self.config.pane_id_format.apply(pane_id),
+                pane.info.title,
+                pane.info.terminal_command.as_deref().unwrap_or_default()
+            );
+        }
+        if !lines.is_empty() {
+            let path = self.config.alerts_log_path();
+            self.append_file(CommandKind::AlertLog, &path, None, &lines);
+        }
+    }
+
+    /// Open a floating pane following the alerts log, or focus the one that
+    /// is already open, so alerts stay in sight when the tracker pane isn't
+    pub(crate) fn open_alerts_pane(&mut self) {
+        let tracker = format!("plugin_{}", get_plugin_ids().plugin_id);
+        let open = self.spawned_by.iter().find(|(id, parent)| {
+            **parent == tracker
+                && self
+                    .panes
+                    .get(&format!("terminal_{}", id))
+                    .is_some_and(|pane| pane.info.title == ALERTS_PANE)
+        });
+        if let Some((&id, _)) = open {
+            focus_terminal_pane(id, true);
+            return;
+        }
+        let path = self.host_path(&self.config.alerts_log_path());
+        let command = match (&self.config.encrypt_cmd, &self.config.decrypt_cmd) {
+            (None, _) => vec![
+                "tail".to_string(),
+                "-n".to_string(),
+                "50".to_string(),
+                "-F".to_string(),
+                path,
+            ],
+            // Rewritten whole when encrypted, so re-read it rather than follow it
+            (Some(_), Some(decrypt)) => self.config.shell.script(
+                &format!(
+                    "while :; do clear; {} < \"$1\" | tail -n 50; sleep 2; done",
+                    decrypt
+                ),
+                [path],
+            ),
+            (Some(_), None) => {
+                self.status =
+                    Some("The alerts log is encrypted and decrypt_cmd isn't set".to_string());
+                return;
+            }
+        };
+        let coordinates = FloatingPaneCoordinates::default()
+            .with_x_percent(55)
+            .with_y_fixed(1)
+            .with_width_percent(45)
+            .with_height_percent(30);
+        open_named_command_pane(
+            &tracker,
+            ALERTS_PANE,
+            &command,
+            None,
+            true,
+            Some(coordinates),
+        );
+        self.status = Some("Opened the alerts pane".to_string());
+    }
+
+    fn alerts_changed(&mut self) {
+        self.save_state();
+        if self.manifest_seen && !self.paused {
+            self.export_to_file();
+        }
+    }
+}
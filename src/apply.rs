@@ -0,0 +1,194 @@
+use crate::commands::{CommandKind, CommandOutcome};
+use crate::{host, layout, terminal_id, State};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use zellij_tile::prelude::rename_terminal_pane;
+
+/// The parts of a names export `apply-names` needs. Anything else in the
+/// file is ignored, so older and newer exports both work.
+#[derive(Deserialize)]
+struct SavedNames {
+    pane_info: BTreeMap<String, SavedPane>,
+}
+
+#[derive(Deserialize)]
+struct SavedPane {
+    name: String,
+    tab: usize,
+    #[serde(default)]
+    position: usize,
+    command: Option<String>,
+    #[serde(default)]
+    is_plugin: bool,
+}
+
+impl State {
+    /// Read a names export and rename the live panes to match it
+    pub(crate) fn read_names_file(&mut self, path: &str) {
+        if self.config.direct_io() {
+            let outcome = match std::fs::read(path) {
+                Ok(bytes) => CommandOutcome {
+                    kind: CommandKind::ReadNames,
+                    stdout: bytes,
+                    error: None,
+                },
+                Err(e) => CommandOutcome {
+                    kind: CommandKind::ReadNames,
+                    stdout: Vec::new(),
+                    error: Some(format!("{}: {}", path, e)),
+                },
+            };
+            self.handle_command_outcome(outcome);
+        } else {
+            self.run_latest(CommandKind::ReadNames, &host::read_file_cmd(path));
+        }
+    }
+
+    /// Rename terminal panes after a names export, JSON or MessagePack.
+    ///
+    /// Terminal ids don't survive a session restart, so each saved pane is
+    /// matched to the live pane at the same tab and position, as long as
+    /// their commands agree. Panes that moved are found by command instead
+    /// when exactly one unclaimed pane runs it.
+    pub(crate) fn apply_names(&mut self, bytes: &[u8]) {
+        let saved: SavedNames = match serde_json::from_slice(bytes)
+            .map_err(|e| e.to_string())
+            .or_else(|json_error| rmp_serde::from_slice(bytes).map_err(|_| json_error))
+        {
+            Ok(saved) => saved,
+            Err(e) => {
+                self.log
+                    .warn(format!("apply-names: unreadable file: {}", e));
+                self.status = Some(format!("apply-names: {}", e));
+                return;
+            }
+        };
+
+        let positions: BTreeMap<(usize, usize), u32> = layout::tab_order(&self.panes)
+            .into_iter()
+            .flat_map(|(tab, panes)| {
+                panes
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(move |(index, id)| Some(((tab + 1, index + 1), terminal_id(&id)?)))
+            })
+            .collect();
+        let command_of = |id: u32| {
+            self.panes
+                .get(&format!("terminal_{}", id))
+                .and_then(|pane| pane.info.terminal_command.clone())
+        };
+        let (renames, unmatched) = match_saved(&saved, &positions, command_of);
+
+        let mut renamed = 0;
+        for (id, name) in renames {
+            let current = self.panes.get(&format!("terminal_{}", id));
+            if current.is_some_and(|pane| pane.info.title != name) {
+                if self.commands.dry_run {
+                    let what = format!("terminal_{} '{}'", id, name);
+                    self.commands.preview("rename", &what);
+                } else {
+                    rename_terminal_pane(id, &name);
+                }
+                renamed += 1;
+            }
+        }
+        let summary = format!(
+            "apply-names: renamed {} panes, {} saved panes not found",
+            renamed, unmatched
+        );
+        self.log.info(summary.clone());
+        self.status = Some(summary);
+    }
+}
+
+/// Pair saved panes with live terminal ids, given the live terminals by
+/// 1-based `(tab, position)` and their commands. Returns the renames and the
+/// number of saved panes left without a match.
+fn match_saved(
+    saved: &SavedNames,
+    positions: &BTreeMap<(usize, usize), u32>,
+    command_of: impl Fn(u32) -> Option<String>,
+) -> (Vec<(u32, String)>, usize) {
+    let mut claimed = BTreeSet::new();
+    let mut renames = Vec::new();
+    let mut moved = Vec::new();
+    for pane in saved.pane_info.values().filter(|pane| !pane.is_plugin) {
+        let in_place = positions
+            .get(&(pane.tab, pane.position))
+            .copied()
+            .filter(|id| command_of(*id) == pane.command);
+        match in_place {
+            Some(id) if claimed.insert(id) => renames.push((id, pane.name.clone())),
+            _ => moved.push(pane),
+        }
+    }
+    // Only once every pane that stayed put has been claimed
+    let mut unmatched = 0;
+    for pane in moved {
+        let mut candidates = positions
+            .values()
+            .copied()
+            .filter(|id| !claimed.contains(id) && command_of(*id) == pane.command);
+        match (candidates.next(), candidates.next()) {
+            (Some(id), None) if pane.command.is_some() => {
+                claimed.insert(id);
+                renames.push((id, pane.name.clone()));
+            }
+            _ => unmatched += 1,
+        }
+    }
+    (renames, unmatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(json: serde_json::Value) -> SavedNames {
+        serde_json::from_value(serde_json::json!({ "pane_info": json })).unwrap()
+    }
+
+    fn commands(id: u32) -> Option<String> {
+        match id {
+            1 | 4 => Some("npm run dev".to_string()),
+            2 => Some("cargo watch".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn panes_in_place_match_by_position_then_moved_ones_by_command() {
+        let saved = saved(serde_json::json!({
+            "terminal_10": { "name": "web", "tab": 1, "position": 1, "command": "npm run dev" },
+            "terminal_11": { "name": "check", "tab": 1, "position": 3, "command": "cargo watch" },
+            "terminal_12": { "name": "shell", "tab": 2, "position": 1, "command": null },
+            "plugin_1": { "name": "tab-bar", "tab": 1, "position": 0, "is_plugin": true },
+        }));
+        let positions = BTreeMap::from([((1, 1), 1), ((1, 2), 2), ((2, 1), 3), ((2, 2), 4)]);
+        let (mut renames, unmatched) = match_saved(&saved, &positions, commands);
+        renames.sort();
+        assert_eq!(
+            renames,
+            vec![
+                (1, "web".to_string()),
+                (2, "check".to_string()),
+                (3, "shell".to_string())
+            ]
+        );
+        assert_eq!(unmatched, 0);
+    }
+
+    #[test]
+    fn ambiguous_or_commandless_moves_are_left_alone() {
+        let saved = saved(serde_json::json!({
+            "terminal_10": { "name": "web", "tab": 3, "position": 1, "command": "npm run dev" },
+            "terminal_11": { "name": "shell", "tab": 3, "position": 2, "command": null },
+            "terminal_12": { "name": "check", "tab": 1, "position": 1, "command": "cargo watch" },
+        }));
+        let positions = BTreeMap::from([((1, 1), 1), ((1, 2), 2), ((2, 1), 3), ((2, 2), 4)]);
+        let (renames, unmatched) = match_saved(&saved, &positions, commands);
+        assert_eq!(renames, vec![(2, "check".to_string())]);
+        assert_eq!(unmatched, 2);
+    }
+}
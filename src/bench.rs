@@ -0,0 +1,175 @@
+use crate::State;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use zellij_tile::prelude::Event;
+
+/// Where one update cycle's time went. Diffing and serialization don't
+/// include the time spent dispatching the commands they issued.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CycleTimings {
+    pub total_us: u64,
+    /// Bringing the tracked panes in line with a manifest
+    pub diff_us: u64,
+    /// Building and encoding the exports
+    pub serialize_us: u64,
+    /// Handing commands to the host
+    pub dispatch_us: u64,
+    /// Commands requested, including writes that replaced a queued one
+    pub commands: u64,
+}
+
+impl CycleTimings {
+    fn add(&mut self, other: &CycleTimings) {
+        self.total_us += other.total_us;
+        self.diff_us += other.diff_us;
+        self.serialize_us += other.serialize_us;
+        self.dispatch_us += other.dispatch_us;
+        self.commands += other.commands;
+    }
+
+    fn divided_by(&self, cycles: u64) -> CycleTimings {
+        let cycles = cycles.max(1);
+        CycleTimings {
+            total_us: self.total_us / cycles,
+            diff_us: self.diff_us / cycles,
+            serialize_us: self.serialize_us / cycles,
+            dispatch_us: self.dispatch_us / cycles,
+            commands: self.commands / cycles,
+        }
+    }
+}
+
+/// Timings of the cycles started by one kind of event
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventTimings {
+    pub cycles: u64,
+    pub last: CycleTimings,
+    pub mean: CycleTimings,
+    /// The cycle with the longest `total_us`
+    pub slowest: CycleTimings,
+    #[serde(skip)]
+    sum: CycleTimings,
+}
+
+/// Per-cycle overhead by event kind (`pane_update`, `timer`, ...), kept
+/// while `benchmark` is on
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct Benchmark {
+    events: BTreeMap<&'static str, EventTimings>,
+}
+
+impl Benchmark {
+    fn record(&mut self, event: &'static str, timings: CycleTimings) {
+        let entry = self.events.entry(event).or_default();
+        entry.cycles += 1;
+        entry.last = timings;
+        entry.sum.add(&timings);
+        entry.mean = entry.sum.divided_by(entry.cycles);
+        if timings.total_us >= entry.slowest.total_us {
+            entry.slowest = timings;
+        }
+    }
+
+    pub fn get(&self, event: &str) -> Option<&EventTimings> {
+        self.events.get(event)
+    }
+}
+
+/// Which part of a cycle a `PhaseTimer` measures
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Diff,
+    Serialize,
+}
+
+/// The update cycle being measured
+#[derive(Debug)]
+pub struct Cycle {
+    started: Instant,
+    /// `Commands::requested` when the cycle started
+    requested: u64,
+    timings: CycleTimings,
+}
+
+/// A phase started within a cycle, with the dispatch time spent so far so
+/// that commands issued during the phase can be left out
+pub struct PhaseTimer {
+    started: Instant,
+    dispatched: Duration,
+}
+
+/// Label of the cycle an event starts
+pub fn event_name(event: &Event) -> &'static str {
+    match event {
+        Event::PaneUpdate(_) => "pane_update",
+        Event::TabUpdate(_) => "tab_update",
+        Event::ModeUpdate(_) => "mode_update",
+        Event::Timer(_) => "timer",
+        Event::Key(_) => "key",
+        Event::RunCommandResult(..) => "command_result",
+        _ => "other",
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u64::MAX as u128) as u64
+}
+
+impl State {
+    /// Start measuring an update cycle, when `benchmark` is on
+    pub(crate) fn begin_cycle(&mut self) {
+        if !self.config.benchmark {
+            return;
+        }
+        self.commands.dispatch_time = Some(Duration::ZERO);
+        self.cycle = Some(Cycle {
+            started: Instant::now(),
+            requested: self.commands.requested,
+            timings: CycleTimings::default(),
+        });
+    }
+
+    /// Finish the cycle started by `begin_cycle` and add it to the stats
+    pub(crate) fn end_cycle(&mut self, event: &'static str) {
+        let dispatched = self.commands.dispatch_time.take().unwrap_or_default();
+        let Some(cycle) = self.cycle.take() else {
+            return;
+        };
+        let timings = CycleTimings {
+            total_us: micros(cycle.started.elapsed()),
+            dispatch_us: micros(dispatched),
+            commands: self.commands.requested - cycle.requested,
+            ..cycle.timings
+        };
+        self.stats
+            .benchmark
+            .get_or_insert_with(Benchmark::default)
+            .record(event, timings);
+    }
+
+    pub(crate) fn start_phase(&self) -> Option<PhaseTimer> {
+        self.cycle.as_ref()?;
+        Some(PhaseTimer {
+            started: Instant::now(),
+            dispatched: self.commands.dispatch_time.unwrap_or_default(),
+        })
+    }
+
+    pub(crate) fn end_phase(&mut self, phase: Phase, timer: Option<PhaseTimer>) {
+        let (Some(timer), Some(cycle)) = (timer, self.cycle.as_mut()) else {
+            return;
+        };
+        let dispatched = self
+            .commands
+            .dispatch_time
+            .unwrap_or_default()
+            .saturating_sub(timer.dispatched);
+        let elapsed = micros(timer.started.elapsed().saturating_sub(dispatched));
+        match phase {
+            Phase::Diff => cycle.timings.diff_us += elapsed,
+            Phase::Serialize => cycle.timings.serialize_us += elapsed,
+        }
+    }
+}
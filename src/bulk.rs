@@ -0,0 +1,238 @@
+use crate::commands::CommandKind;
+use crate::ui::UiMode;
+use crate::{terminal_id, State};
+use std::fmt::Write;
+use zellij_tile::prelude::*;
+
+/// Something done to every marked pane at once, picked from the `x` menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkAction {
+    Capture,
+    Tag,
+    Group,
+    Bundle,
+    Close,
+}
+
+impl BulkAction {
+    pub const ALL: [BulkAction; 5] = [
+        BulkAction::Capture,
+        BulkAction::Tag,
+        BulkAction::Group,
+        BulkAction::Bundle,
+        BulkAction::Close,
+    ];
+
+    pub fn key(self) -> char {
+        match self {
+            BulkAction::Capture => 'c',
+            BulkAction::Tag => 't',
+            BulkAction::Group => 'g',
+            BulkAction::Bundle => 'B',
+            BulkAction::Close => 'X',
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            BulkAction::Capture => "capture now",
+            BulkAction::Tag => "add a tag",
+            BulkAction::Group => "move into a new tab",
+            BulkAction::Bundle => "write their captures to zj-bundle.txt",
+            BulkAction::Close => "close",
+        }
+    }
+}
+
+/// The last `lines` lines of `content`
+fn tail_lines(content: &str, lines: usize) -> &str {
+    let trimmed = content.trim_end_matches('\n');
+    match trimmed.rmatch_indices('\n').nth(lines.saturating_sub(1)) {
+        Some((newline, _)) => &content[newline + 1..],
+        None => content,
+    }
+}
+
+impl State {
+    /// Mark or unmark the selected pane and move on to the next one
+    pub(crate) fn toggle_mark(&mut self) {
+        let Some(pane_id) = self.selected_pane_id().cloned() else {
+            return;
+        };
+        if !self.marked.remove(&pane_id) {
+            self.marked.insert(pane_id);
+        }
+        self.move_selection(1);
+    }
+
+    /// The marked panes that still exist, or the selected one when nothing
+    /// is marked
+    pub(crate) fn bulk_targets(&self) -> Vec<String> {
+        let marked: Vec<String> = self
+            .marked
+            .iter()
+            .filter(|id| self.panes.contains_key(*id))
+            .cloned()
+            .collect();
+        if marked.is_empty() {
+            self.selected_pane_id().cloned().into_iter().collect()
+        } else {
+            marked
+        }
+    }
+
+    pub(crate) fn run_bulk(&mut self, action: BulkAction) {
+        let targets = self.bulk_targets();
+        if targets.is_empty() {
+            return;
+        }
+        let count = targets.len();
+        self.status = Some(match action {
+            BulkAction::Capture => {
+                if self.config.minimal_permissions {
+                    "Capturing needs RunCommands".to_string()
+                } else {
+                    let ids: Vec<u32> = targets.iter().filter_map(|id| terminal_id(id)).collect();
+                    for id in &ids {
+                        self.capture_pane(*id);
+                    }
+                    format!("Capturing {} panes", ids.len())
+                }
+            }
+            // Asks for the tag first, see `tag_panes`
+            BulkAction::Tag => {
+                self.mode = UiMode::Tag(String::new());
+                return;
+            }
+            // Like closing, leaves plugin panes and the tracker where they are
+            BulkAction::Group => {
+                let terminals: Vec<&String> = targets
+                    .iter()
+                    .filter(|id| terminal_id(id).is_some())
+                    .collect();
+                let ids: Vec<PaneId> = terminals
+                    .iter()
+                    .filter_map(|id| terminal_id(id).map(PaneId::Terminal))
+                    .collect();
+                if ids.is_empty() {
+                    "Only terminal panes can be moved".to_string()
+                } else if self.commands.dry_run {
+                    let what: Vec<&str> = terminals.iter().map(|id| id.as_str()).collect();
+                    self.commands.preview("move to new tab", &what.join(" "));
+                    format!("Dry run: not moving {} panes", ids.len())
+                } else {
+                    break_panes_to_new_tab(&ids, None, true);
+                    format!("Moved {} panes to a new tab", ids.len())
+                }
+            }
+            BulkAction::Bundle => self.write_bundle(&targets),
+            // Plugin panes, the tracker's own included, are never closed.
+            // Asks for confirmation first, see `close_panes`
+            BulkAction::Close => {
+                let terminals: Vec<String> = targets
+                    .into_iter()
+                    .filter(|id| terminal_id(id).is_some())
+                    .collect();
+                if terminals.is_empty() {
+                    "Only terminal panes can be closed".to_string()
+                } else {
+                    self.mode = UiMode::ConfirmClose(terminals);
+                    return;
+                }
+            }
+        });
+        self.log
+            .info(format!("bulk {:?} on {} panes", action, count));
+        self.marked.clear();
+    }
+
+    /// Close the terminal panes confirmed in `UiMode::ConfirmClose`
+    pub(crate) fn close_panes(&mut self, targets: &[String]) {
+        let ids: Vec<PaneId> = targets
+            .iter()
+            .filter_map(|id| terminal_id(id).map(PaneId::Terminal))
+            .collect();
+        let count = ids.len();
+        self.status = Some(if self.commands.dry_run {
+            self.commands.preview("close", &targets.join(" "));
+            format!("Dry run: not closing {} panes", count)
+        } else {
+            close_multiple_panes(ids);
+            format!("Closed {} panes", count)
+        });
+        self.log.info(format!("bulk Close on {} panes", count));
+        self.marked.clear();
+    }
+
+    /// Add `tag` to every marked pane
+    pub(crate) fn tag_panes(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return;
+        }
+        let targets = self.bulk_targets();
+        for pane_id in &targets {
+            self.tags
+                .entry(pane_id.clone())
+                .or_default()
+                .insert(tag.to_string());
+        }
+        self.status = Some(format!("Tagged {} panes '{}'", targets.len(), tag));
+        self.marked.clear();
+        self.save_state();
+        if self.manifest_seen && !self.paused {
+            self.export_to_file();
+        }
+    }
+
+    /// Write the latest captures of `targets` into one file, for pasting
+    /// into a chat or a bug report
+    fn write_bundle(&mut self, targets: &[String]) -> String {
+        let (bundle, included) = self.render_bundle(targets, None);
+        if included == 0 {
+            return "None of these panes has been captured yet".to_string();
+        }
+        let path = self.config.bundle_path();
+        self.write_file(CommandKind::Bundle, &path, &bundle);
+        format!("Bundled {} panes into {}", included, self.host_path(&path))
+    }
+
+    /// The latest captures of `targets`, each under a header naming the
+    /// pane, or only their last `lines` lines. Also returns how many of the
+    /// panes had been captured.
+    pub(crate) fn render_bundle(
+        &self,
+        targets: &[String],
+        lines: Option<usize>,
+    ) -> (String, usize) {
+        let mut bundle = String::new();
+        let mut included = 0;
+        for pane_id in targets {
+            let Some(content) = terminal_id(pane_id).and_then(|id| self.pane_content.get(&id))
+            else {
+                continue;
+            };
+            let pane = &self.panes[pane_id];
+            let _ = writeln!(
+                bundle,
+                "===== {} ({}) =====",
+                pane.info.title,
+                self.config.pane_id_format.apply(pane_id)
+            );
+            if let Some(ref command) = pane.info.terminal_command {
+                let _ = writeln!(bundle, "$ {}", command);
+            }
+            let content = match lines {
+                Some(lines) => tail_lines(content, lines),
+                None => content,
+            };
+            bundle.push_str(content);
+            if !content.ends_with('\n') {
+                bundle.push('\n');
+            }
+            bundle.push('\n');
+            included += 1;
+        }
+        (bundle, included)
+    }
+}
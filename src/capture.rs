@@ -0,0 +1,360 @@
+use crate::config::Config;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Pane fields the scheduler needs to pick an interval
+pub struct CaptureCandidate<'a> {
+    pub terminal_id: u32,
+    pub name: &'a str,
+    pub command: Option<&'a str>,
+}
+
+/// Consecutive changed captures before a pane starts backing off
+const BACKOFF_AFTER: u32 = 3;
+
+/// Per-pane state for slowing down captures of panes that never settle
+#[derive(Default)]
+struct Backoff {
+    /// Multiplier applied to the pane's interval, a power of two
+    factor: u64,
+    /// Captures in a row whose content differed from the previous one
+    changed_streak: u32,
+}
+
+/// Length of the window `max_captures_per_minute` and
+/// `max_capture_bytes_per_minute` are counted over
+const BUDGET_WINDOW_SECS: u64 = 60;
+
+/// Captures started and bytes written over the last minute
+#[derive(Default)]
+struct Budget {
+    captures: VecDeque<u64>,
+    written: VecDeque<(u64, usize)>,
+}
+
+impl Budget {
+    fn expire(&mut self, now: u64) {
+        let fresh = |at: u64| now.saturating_sub(at) < BUDGET_WINDOW_SECS;
+        while self.captures.front().is_some_and(|at| !fresh(*at)) {
+            self.captures.pop_front();
+        }
+        while self.written.front().is_some_and(|(at, _)| !fresh(*at)) {
+            self.written.pop_front();
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        self.written.iter().map(|(_, bytes)| bytes).sum()
+    }
+}
+
+/// Decides which panes are due for a content capture
+///
+/// Every pane gets the global `capture_interval` unless a `capture_rules`
+/// entry matches it, in which case the rule's interval is used instead.
+/// Panes whose content changes on every capture have that interval doubled
+/// (up to `capture_backoff_max` times) until a capture comes back unchanged.
+///
+/// On top of that the session shares `max_captures_per_minute` and
+/// `max_capture_bytes_per_minute`. When more panes are due than the budget
+/// allows, the ones captured longest ago go first and the rest stay due.
+#[derive(Default)]
+pub struct CaptureScheduler {
+    last_capture: BTreeMap<u32, u64>,
+    backoff: BTreeMap<u32, Backoff>,
+    budget: Budget,
+    /// Due panes the budget held back at the latest tick
+    deferred: usize,
+}
+
+impl CaptureScheduler {
+    /// Panes whose interval has elapsed at `now` and that fit in the
+    /// budget; they are recorded as captured
+    pub fn take_due(&mut self, config: &Config, panes: &[CaptureCandidate], now: u64) -> Vec<u32> {
+        let mut due = Vec::new();
+        for pane in panes {
+            let interval = config.capture_interval_for(pane.name, pane.command);
+            if interval == 0 {
+                continue;
+            }
+            let interval = interval * self.factor(pane.terminal_id);
+            let last = self.last_capture.get(&pane.terminal_id).copied();
+            if last.is_none_or(|last| now.saturating_sub(last) >= interval) {
+                due.push((last, pane.terminal_id));
+            }
+        }
+        // Never captured sorts first, then longest ago
+        due.sort();
+        let allowed = self.allowed(config, now).min(due.len());
+        self.deferred = due.len() - allowed;
+        let due: Vec<u32> = due.into_iter().take(allowed).map(|(_, id)| id).collect();
+        for id in &due {
+            self.last_capture.insert(*id, now);
+            self.budget.captures.push_back(now);
+        }
+        self.last_capture
+            .retain(|id, _| panes.iter().any(|p| p.terminal_id == *id));
+        self.backoff
+            .retain(|id, _| panes.iter().any(|p| p.terminal_id == *id));
+        due
+    }
+
+    /// How many more captures the budget allows at `now`
+    fn allowed(&mut self, config: &Config, now: u64) -> usize {
+        self.budget.expire(now);
+        let max_bytes = config.max_capture_bytes_per_minute;
+        if max_bytes > 0 && self.budget.bytes() >= max_bytes {
+            return 0;
+        }
+        match config.max_captures_per_minute {
+            0 => usize::MAX,
+            max => max.saturating_sub(self.budget.captures.len()),
+        }
+    }
+
+    /// Count `bytes` of capture about to be written against
+    /// `max_capture_bytes_per_minute`. Returns false, counting nothing, when
+    /// they don't fit in what's left of it.
+    pub fn spend_bytes(&mut self, config: &Config, bytes: usize, now: u64) -> bool {
+        self.budget.expire(now);
+        let max_bytes = config.max_capture_bytes_per_minute;
+        if max_bytes > 0 && self.budget.bytes() + bytes > max_bytes {
+            return false;
+        }
+        self.budget.written.push_back((now, bytes));
+        true
+    }
+
+    /// Due panes the budget held back at the latest tick
+    pub fn deferred(&self) -> usize {
+        self.deferred
+    }
+
+    /// Feed back whether a pane's latest capture differed from the one
+    /// before. Returns the new interval multiplier when it changed.
+    pub fn record(&mut self, terminal_id: u32, changed: bool, max_factor: u64) -> Option<u64> {
+        let backoff = self.backoff.entry(terminal_id).or_default();
+        let previous = backoff.factor.max(1);
+        if changed {
+            backoff.changed_streak += 1;
+            if backoff.changed_streak >= BACKOFF_AFTER {
+                backoff.factor = (previous * 2).min(max_factor.max(1));
+            }
+        } else {
+            backoff.changed_streak = 0;
+            backoff.factor = 1;
+        }
+        (backoff.factor.max(1) != previous).then_some(backoff.factor.max(1))
+    }
+
+    /// Current interval multiplier of a pane, 1 when it isn't backed off
+    pub fn factor(&self, terminal_id: u32) -> u64 {
+        self.backoff
+            .get(&terminal_id)
+            .map_or(1, |backoff| backoff.factor.max(1))
+    }
+
+    /// Number of panes currently captured less often than configured
+    pub fn backed_off(&self) -> usize {
+        self.backoff.values().filter(|b| b.factor > 1).count()
+    }
+
+    /// How often the timer should fire: the shortest configured interval
+    pub fn tick(config: &Config) -> f64 {
+        std::iter::once(config.capture_interval)
+            .chain(config.capture_rules.iter().map(|r| r.interval))
+            .filter(|i| *i > 0)
+            .min()
+            .unwrap_or(1) as f64
+    }
+}
+
+/// Keep only the end of `content` when it's longer than `max_bytes`,
+/// starting at a line boundary. Returns `None` when it already fits.
+pub fn truncate_tail(content: &str, max_bytes: usize) -> Option<&str> {
+    if max_bytes == 0 || content.len() <= max_bytes {
+        return None;
+    }
+    let mut start = content.len() - max_bytes;
+    while !content.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &content[start..];
+    // Drop the partial first line unless that would leave nothing
+    Some(match tail.find('\n') {
+        Some(newline) if newline + 1 < tail.len() => &tail[newline + 1..],
+        _ => tail,
+    })
+}
+
+/// How a pane is captured and stored, from `capture_format` or a matching
+/// `capture_format_rules` entry, e.g. `full,ansi,gzip`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureFormat {
+    /// The whole scrollback rather than what's on screen
+    pub full: bool,
+    /// Keep terminal escape sequences (colors etc.) instead of stripping them
+    pub ansi: bool,
+    /// Write the capture file gzipped, as `.txt.gz`
+    pub gzip: bool,
+}
+
+impl CaptureFormat {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut format = CaptureFormat::default();
+        for word in spec
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|w| !w.is_empty())
+        {
+            match word {
+                "full" => format.full = true,
+                "viewport" => format.full = false,
+                "ansi" => format.ansi = true,
+                "plain" => format.ansi = false,
+                "gzip" => format.gzip = true,
+                "uncompressed" => format.gzip = false,
+                other => return Err(format!("unknown capture format '{}'", other)),
+            }
+        }
+        Ok(format)
+    }
+}
+
+/// Remove terminal escape sequences: CSI (`ESC [ ... m` and friends), OSC
+/// (`ESC ] ... BEL`) and two-character escapes
+pub fn strip_ansi(content: &str) -> Cow<'_, str> {
+    if !content.contains('\u{1b}') {
+        return Cow::Borrowed(content);
+    }
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // Parameters and intermediates up to a final byte in @..~
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                // Up to BEL or the `ESC \` string terminator
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Command line that prints a terminal pane's viewport, or its whole
+/// scrollback with `full`, on stdout
+pub fn capture_cmd(terminal_id: u32, format: CaptureFormat) -> Vec<String> {
+    let mut argv = vec![
+        "zellij".to_string(),
+        "action".to_string(),
+        "dump-pane".to_string(),
+    ];
+    if format.full {
+        argv.push("--full".to_string());
+    }
+    argv.push(terminal_id.to_string());
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pane(terminal_id: u32) -> CaptureCandidate<'static> {
+        CaptureCandidate {
+            terminal_id,
+            name: "build",
+            command: None,
+        }
+    }
+
+    fn every(seconds: u64) -> Config {
+        Config {
+            capture_interval: seconds,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn unsettled_panes_back_off_until_a_capture_repeats() {
+        let mut scheduler = CaptureScheduler::default();
+        assert_eq!(scheduler.record(1, true, 8), None);
+        assert_eq!(scheduler.record(1, true, 8), None);
+        assert_eq!(scheduler.record(1, true, 8), Some(2));
+        assert_eq!(scheduler.record(1, true, 8), Some(4));
+        assert_eq!(scheduler.record(1, true, 8), Some(8));
+        assert_eq!(scheduler.record(1, true, 8), None);
+        assert_eq!(scheduler.backed_off(), 1);
+        assert_eq!(scheduler.record(1, false, 8), Some(1));
+        assert_eq!(scheduler.factor(1), 1);
+        assert_eq!(scheduler.backed_off(), 0);
+    }
+
+    #[test]
+    fn backed_off_panes_are_due_less_often() {
+        let config = every(10);
+        let mut scheduler = CaptureScheduler::default();
+        let panes = [pane(1), pane(2)];
+        assert_eq!(scheduler.take_due(&config, &panes, 100), vec![1, 2]);
+        for _ in 0..BACKOFF_AFTER {
+            scheduler.record(1, true, 4);
+        }
+        assert_eq!(scheduler.take_due(&config, &panes, 110), vec![2]);
+        assert_eq!(scheduler.take_due(&config, &panes, 120), vec![1, 2]);
+
+        // A closed pane's backoff is forgotten
+        scheduler.take_due(&config, &panes[1..], 130);
+        assert_eq!(scheduler.factor(1), 1);
+    }
+
+    #[test]
+    fn capture_budget_defers_the_most_recently_captured() {
+        let config = Config {
+            max_captures_per_minute: 2,
+            ..every(10)
+        };
+        let mut scheduler = CaptureScheduler::default();
+        let panes = [pane(1), pane(2), pane(3)];
+        assert_eq!(scheduler.take_due(&config, &panes, 100), vec![1, 2]);
+        assert_eq!(scheduler.deferred(), 1);
+        assert!(scheduler.take_due(&config, &panes, 130).is_empty());
+        assert_eq!(scheduler.deferred(), 3);
+        // Pane 3 was never captured, so it goes before the others
+        assert_eq!(scheduler.take_due(&config, &panes, 160), vec![3, 1]);
+        assert_eq!(scheduler.deferred(), 1);
+    }
+
+    #[test]
+    fn byte_budget_refuses_writes_that_dont_fit() {
+        let config = Config {
+            max_capture_bytes_per_minute: 100,
+            ..every(10)
+        };
+        let mut scheduler = CaptureScheduler::default();
+        assert!(scheduler.spend_bytes(&config, 60, 100));
+        assert!(!scheduler.spend_bytes(&config, 60, 110));
+        assert!(scheduler.spend_bytes(&config, 40, 110));
+        assert!(scheduler.take_due(&config, &[pane(1)], 120).is_empty());
+        assert_eq!(scheduler.deferred(), 1);
+        assert!(scheduler.spend_bytes(&config, 60, 160));
+    }
+}
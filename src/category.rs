@@ -0,0 +1,123 @@
+use regex::Regex;
+
+/// Foreground colors available to category rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "red" => Some(Color::Red),
+            "green" => Some(Color::Green),
+            "yellow" => Some(Color::Yellow),
+            "blue" => Some(Color::Blue),
+            "magenta" | "purple" => Some(Color::Magenta),
+            "cyan" => Some(Color::Cyan),
+            "white" => Some(Color::White),
+            _ => None,
+        }
+    }
+
+    /// Wrap `text` in the ANSI escape codes for this color
+    pub fn paint(&self, text: &str) -> String {
+        let code = match self {
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+            Color::White => 37,
+        };
+        format!("\u{1b}[{}m{}\u{1b}[0m", code, text)
+    }
+}
+
+/// Assigns panes whose command matches `pattern` to a named category,
+/// written as `<regex>=<category>` or `<regex>=<category>:<color>`
+#[derive(Debug, Clone)]
+pub struct CategoryRule {
+    pub pattern: Regex,
+    pub name: String,
+    pub color: Option<Color>,
+}
+
+/// Rules used when `category_rules` isn't configured
+const DEFAULT_RULES: &str = "^(n?vim?|hx|helix|emacs|nano|micro)\\b=editor:blue; \
+    ^(cargo|make|just|npm|pnpm|yarn|bun|go|gradle|mvn)\\b=build:yellow; \
+    ^(git|lazygit|tig|gitui)\\b=vcs:magenta; \
+    ^(htop|btop|top|tail|journalctl|less)\\b=monitor:cyan; \
+    ^(ssh|mosh)\\b=remote:green";
+
+impl CategoryRule {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (pattern, target) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| format!("'{}' is missing '=<category>'", spec))?;
+        let (name, color) = match target.split_once(':') {
+            Some((name, color)) => {
+                let color = Color::parse(color)
+                    .ok_or_else(|| format!("unknown color '{}'", color.trim()))?;
+                (name, Some(color))
+            }
+            None => (target, None),
+        };
+        let pattern = Regex::new(pattern.trim()).map_err(|e| e.to_string())?;
+        Ok(CategoryRule {
+            pattern,
+            name: name.trim().to_string(),
+            color,
+        })
+    }
+
+    /// Parse a `;`-separated rule list, collecting errors for invalid entries
+    pub fn parse_list(specs: &str) -> (Vec<Self>, Vec<String>) {
+        let mut rules = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            match CategoryRule::parse(spec) {
+                Ok(rule) => rules.push(rule),
+                Err(e) => errors.push(e),
+            }
+        }
+        (rules, errors)
+    }
+
+    pub fn defaults() -> Vec<Self> {
+        CategoryRule::parse_list(DEFAULT_RULES).0
+    }
+}
+
+/// First rule matching the pane's command, or its title when it has no command.
+/// A leading path on the program is dropped, so `/usr/bin/nvim` matches `^nvim`.
+pub fn categorize<'a>(
+    rules: &'a [CategoryRule],
+    title: &str,
+    command: Option<&str>,
+) -> Option<&'a CategoryRule> {
+    let subject = strip_program_path(command.unwrap_or(title).trim());
+    rules.iter().find(|rule| rule.pattern.is_match(subject))
+}
+
+/// `line` with any directory dropped from its first word
+fn strip_program_path(line: &str) -> &str {
+    let program_end = line.find(char::is_whitespace).unwrap_or(line.len());
+    match line[..program_end].rfind('/') {
+        Some(slash) => &line[slash + 1..],
+        None => line,
+    }
+}
+
+/// Bare program name of a command line, e.g. `nvim` for `/usr/bin/nvim src/lib.rs`
+pub fn program_name(line: &str) -> &str {
+    let line = strip_program_path(line.trim());
+    line.split_whitespace().next().unwrap_or("")
+}
@@ -0,0 +1,488 @@
+use crate::hooks::HookEvent;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+use zellij_tile::prelude::*;
+
+/// Context key used to recognise our own commands in `RunCommandResult`
+const CONTEXT_ID: &str = "pane_tracker_cmd";
+
+/// How long a command may hold a concurrency slot before it's set aside as
+/// overdue, so a slow one doesn't stall the queue. Its result is still
+/// handled when it arrives.
+const SLOT_SECS: u64 = 30;
+/// The same for git commands, which can be slow on big export directories
+const GIT_SLOT_SECS: u64 = 120;
+/// The same for commands from the user's configuration
+const USER_SLOT_SECS: u64 = 300;
+
+/// Overdue commands of the tracker's own that haven't reported back after
+/// this long are taken as lost and counted as failed
+const LOST_AFTER_SECS: u64 = 3600;
+
+/// What a host command was issued for, so its result can be routed back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// Appending buffered lines to the log file
+    Log,
+    /// Writing the names JSON
+    Export,
+    /// Writing the changed-pane summary
+    Changes,
+    /// Reading the config file
+    ReadConfigFile,
+    /// Listing the snapshots of a terminal pane's capture
+    SnapshotLog(u32),
+    /// Diffing two snapshots of a terminal pane's capture
+    SnapshotDiff(u32),
+    /// Writing the JSON Schema of the names export
+    Schema,
+    /// Writing the change marker file
+    Marker,
+    /// Writing the layout diagram
+    Layout,
+    /// Writing the Graphviz session graph
+    Dot,
+    /// Writing the shell-sourceable pane variables
+    Env,
+    /// Writing the captures of the marked panes as one file
+    Bundle,
+    /// Writing the heartbeat file
+    Heartbeat,
+    /// Dumping a terminal pane's content, returned on stdout
+    Capture(u32),
+    /// Writing a terminal pane's captured content to disk
+    WriteCapture(u32),
+    /// Dumping a pane for the `dump` pipe request with this number
+    Dump(u64),
+    /// Writing the content of a `dump` request to its `out` file
+    DumpWrite(u64),
+    /// Piping a terminal pane's capture into the clipboard command
+    Clipboard(u32),
+    /// Reading the redaction rules file, returned on stdout
+    ReadRedactRules,
+    /// Reading the persisted state file, returned on stdout
+    ReadState,
+    /// Writing the persisted state file
+    SaveState,
+    /// Reading a names export for `apply-names`, returned on stdout
+    ReadNames,
+    /// Looking up the repo and branch of a terminal pane's cwd
+    GitInfo(u32),
+    /// Committing the export directory to its git repository
+    SnapshotCommit,
+    /// Running `upload_cmd` after an export cycle
+    Upload,
+    /// Looking up the host name, returned on stdout
+    Hostname,
+    /// Appending watched commands to the command log
+    CommandLog,
+    /// Appending to the alerts log
+    AlertLog,
+    /// Appending a row to the focus timeline
+    FocusTimeline,
+    /// Appending to the `dry_run` preview log
+    DryRunLog,
+    /// A user hook for a pane event
+    Hook(HookEvent),
+    /// Publishing a pane event to MQTT
+    Publish(HookEvent),
+}
+
+impl fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandKind::Log => write!(f, "log"),
+            CommandKind::Export => write!(f, "export"),
+            CommandKind::Changes => write!(f, "changes"),
+            CommandKind::ReadConfigFile => write!(f, "read-config-file"),
+            CommandKind::SnapshotLog(id) => write!(f, "snapshot-log terminal_{}", id),
+            CommandKind::SnapshotDiff(id) => write!(f, "snapshot-diff terminal_{}", id),
+            CommandKind::Schema => write!(f, "schema"),
+            CommandKind::Marker => write!(f, "marker"),
+            CommandKind::Layout => write!(f, "layout"),
+            CommandKind::Dot => write!(f, "dot"),
+            CommandKind::Env => write!(f, "env"),
+            CommandKind::Bundle => write!(f, "bundle"),
+            CommandKind::Heartbeat => write!(f, "heartbeat"),
+            CommandKind::Capture(id) => write!(f, "capture terminal_{}", id),
+            CommandKind::WriteCapture(id) => write!(f, "write terminal_{}", id),
+            CommandKind::Dump(id) => write!(f, "dump #{}", id),
+            CommandKind::DumpWrite(id) => write!(f, "write dump #{}", id),
+            CommandKind::Clipboard(id) => write!(f, "clipboard terminal_{}", id),
+            CommandKind::ReadRedactRules => write!(f, "read redaction rules"),
+            CommandKind::ReadState => write!(f, "read state"),
+            CommandKind::SaveState => write!(f, "save state"),
+            CommandKind::ReadNames => write!(f, "read names"),
+            CommandKind::GitInfo(id) => write!(f, "git info terminal_{}", id),
+            CommandKind::SnapshotCommit => write!(f, "snapshot commit"),
+            CommandKind::Upload => write!(f, "upload"),
+            CommandKind::Hostname => write!(f, "hostname"),
+            CommandKind::CommandLog => write!(f, "command log"),
+            CommandKind::AlertLog => write!(f, "alert log"),
+            CommandKind::FocusTimeline => write!(f, "focus timeline"),
+            CommandKind::DryRunLog => write!(f, "dry-run log"),
+            CommandKind::Hook(event) => write!(f, "hook {}", event),
+            CommandKind::Publish(event) => write!(f, "publish {}", event),
+        }
+    }
+}
+
+impl CommandKind {
+    /// Commands from the user's configuration (`upload_cmd`, hooks, MQTT,
+    /// the clipboard command), which may run as long as they like
+    pub fn user_supplied(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::Upload
+                | CommandKind::Hook(_)
+                | CommandKind::Publish(_)
+                | CommandKind::Clipboard(_)
+        )
+    }
+
+    fn slot_secs(&self) -> u64 {
+        match self {
+            _ if self.user_supplied() => USER_SLOT_SECS,
+            CommandKind::SnapshotCommit
+            | CommandKind::SnapshotLog(_)
+            | CommandKind::SnapshotDiff(_)
+            | CommandKind::GitInfo(_) => GIT_SLOT_SECS,
+            _ => SLOT_SECS,
+        }
+    }
+
+    /// Whether the command still runs under `dry_run`: reads the tracker
+    /// needs to render what it would do, and its own two logs
+    pub fn runs_in_dry_run(&self) -> bool {
+        matches!(
+            self,
+            CommandKind::Log
+                | CommandKind::DryRunLog
+                | CommandKind::ReadConfigFile
+                | CommandKind::SnapshotLog(_)
+                | CommandKind::SnapshotDiff(_)
+                | CommandKind::Capture(_)
+                | CommandKind::Dump(_)
+                | CommandKind::ReadRedactRules
+                | CommandKind::ReadState
+                | CommandKind::ReadNames
+                | CommandKind::GitInfo(_)
+                | CommandKind::Hostname
+        )
+    }
+}
+
+/// Longest argument shown in full in a `dry_run` preview line
+const PREVIEW_ARG_LEN: usize = 120;
+
+/// How a command would have been run, for the `dry_run` preview log
+fn preview_line(kind: &CommandKind, argv: &[String]) -> String {
+    let args: Vec<String> = argv
+        .iter()
+        .map(|arg| match arg.char_indices().nth(PREVIEW_ARG_LEN) {
+            Some((end, _)) => format!("{:?}...({} bytes)", &arg[..end], arg.len()),
+            None => format!("{:?}", arg),
+        })
+        .collect();
+    format!("[{}] {} {}\n", crate::unix_now(), kind, args.join(" "))
+}
+
+/// A command waiting for a free slot
+struct QueuedCommand {
+    id: u64,
+    kind: CommandKind,
+    argv: Vec<String>,
+}
+
+/// A command that was issued and hasn't reported back yet
+struct PendingCommand {
+    kind: CommandKind,
+    issued_at: u64,
+}
+
+/// Outcome of a finished command, matched back to the kind it was issued with
+pub struct CommandOutcome {
+    pub kind: CommandKind,
+    pub stdout: Vec<u8>,
+    pub error: Option<String>,
+}
+
+/// Issues host commands and correlates their results
+///
+/// At most `max_in_flight` commands run at once; the rest wait in a FIFO queue
+/// that is drained as results arrive, so a burst of captures in a big session
+/// doesn't fork dozens of processes at the same time.
+pub struct Commands {
+    next_id: u64,
+    max_in_flight: usize,
+    queue: VecDeque<QueuedCommand>,
+    pending: BTreeMap<u64, PendingCommand>,
+    /// Commands still running past their slot time, no longer counted
+    /// against `max_in_flight`
+    overdue: BTreeMap<u64, PendingCommand>,
+    pub failures: u64,
+    pub last_error: Option<String>,
+    /// Record commands in `previews` instead of running them, except the
+    /// kinds that `runs_in_dry_run`
+    pub dry_run: bool,
+    previews: Vec<String>,
+    /// Commands requested so far, including writes that replaced a queued one
+    pub requested: u64,
+    /// Time spent handing commands to the host, counted while `benchmark`
+    /// measures a cycle
+    pub dispatch_time: Option<Duration>,
+    /// Every command requested, for tests to inspect
+    #[cfg(test)]
+    pub issued: Vec<(CommandKind, Vec<String>)>,
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Commands::new(crate::config::DEFAULT_MAX_CONCURRENT_COMMANDS)
+    }
+}
+
+impl Commands {
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.max_in_flight = max_in_flight.max(1);
+    }
+
+    pub fn new(max_in_flight: usize) -> Self {
+        Commands {
+            next_id: 0,
+            max_in_flight: max_in_flight.max(1),
+            queue: VecDeque::new(),
+            pending: BTreeMap::new(),
+            overdue: BTreeMap::new(),
+            failures: 0,
+            last_error: None,
+            dry_run: false,
+            previews: Vec::new(),
+            requested: 0,
+            dispatch_time: None,
+            #[cfg(test)]
+            issued: Vec::new(),
+        }
+    }
+
+    /// Queue `argv` to run on the host, tagged so the result can be matched to `kind`
+    pub fn run(&mut self, kind: CommandKind, argv: &[String]) {
+        if self.dry_run && !kind.runs_in_dry_run() {
+            self.previews.push(preview_line(&kind, argv));
+            return;
+        }
+        self.requested += 1;
+        #[cfg(test)]
+        self.issued.push((kind.clone(), argv.to_vec()));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queue.push_back(QueuedCommand {
+            id,
+            kind,
+            argv: argv.to_vec(),
+        });
+        self.pump();
+    }
+
+    /// Like `run`, but replaces a still-queued command of the same kind.
+    /// Used for writes where only the latest contents matter.
+    pub fn run_latest(&mut self, kind: CommandKind, argv: &[String]) {
+        if let Some(queued) = self.queue.iter_mut().find(|q| q.kind == kind) {
+            queued.argv = argv.to_vec();
+            self.requested += 1;
+            #[cfg(test)]
+            self.issued.push((kind, argv.to_vec()));
+            return;
+        }
+        self.run(kind, argv);
+    }
+
+    /// Record a side effect that `dry_run` held back and that didn't go
+    /// through a host command: a direct write, or a Zellij action labelled
+    /// by what it does (`rename`, `close`, ...)
+    pub fn preview(&mut self, label: impl fmt::Display, what: &str) {
+        self.previews
+            .push(format!("[{}] {} {}\n", crate::unix_now(), label, what));
+    }
+
+    /// Drain the `dry_run` preview lines, if any
+    pub fn take_previews(&mut self) -> Option<String> {
+        if self.previews.is_empty() {
+            return None;
+        }
+        let lines = self.previews.concat();
+        self.previews.clear();
+        Some(lines)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn overdue(&self) -> usize {
+        self.overdue.len()
+    }
+
+    /// Match a `RunCommandResult` to the command that produced it.
+    /// Returns `None` for results that don't belong to us.
+    pub fn handle_result(
+        &mut self,
+        exit_code: Option<i32>,
+        stdout: Vec<u8>,
+        stderr: &[u8],
+        context: &BTreeMap<String, String>,
+    ) -> Option<CommandOutcome> {
+        let id = context.get(CONTEXT_ID)?.parse::<u64>().ok()?;
+        let pending = match self.pending.remove(&id) {
+            Some(pending) => pending,
+            None => self.overdue.remove(&id)?,
+        };
+        self.pump();
+
+        let error = match exit_code {
+            Some(0) => None,
+            code => {
+                let stderr = String::from_utf8_lossy(stderr);
+                let detail = stderr.lines().rev().find(|l| !l.trim().is_empty());
+                let message = match (code, detail) {
+                    (_, Some(line)) => line.trim().to_string(),
+                    (Some(code), None) => format!("exit code {}", code),
+                    (None, None) => "terminated without exit code".to_string(),
+                };
+                Some(format!("{}: {}", pending.kind, message))
+            }
+        };
+
+        if let Some(ref error) = error {
+            self.record_failure(error.clone());
+        }
+
+        Some(CommandOutcome {
+            kind: pending.kind,
+            stdout,
+            error,
+        })
+    }
+
+    /// Dispatch queued commands while there are free slots
+    fn pump(&mut self) {
+        let now = crate::unix_now();
+        let slow: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| now.saturating_sub(p.issued_at) >= p.kind.slot_secs())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in slow {
+            if let Some(slow) = self.pending.remove(&id) {
+                self.overdue.insert(id, slow);
+            }
+        }
+        let lost: Vec<u64> = self
+            .overdue
+            .iter()
+            .filter(|(_, p)| {
+                !p.kind.user_supplied() && now.saturating_sub(p.issued_at) >= LOST_AFTER_SECS
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in lost {
+            if let Some(lost) = self.overdue.remove(&id) {
+                self.record_failure(format!(
+                    "{}: no result after {}s",
+                    lost.kind, LOST_AFTER_SECS
+                ));
+            }
+        }
+
+        while self.pending.len() < self.max_in_flight {
+            let Some(command) = self.queue.pop_front() else {
+                break;
+            };
+            let mut context = BTreeMap::new();
+            context.insert(CONTEXT_ID.to_string(), command.id.to_string());
+            let args: Vec<&str> = command.argv.iter().map(|s| s.as_str()).collect();
+            let started = self.dispatch_time.map(|_| Instant::now());
+            run_command(&args, context);
+            if let (Some(total), Some(started)) = (self.dispatch_time.as_mut(), started) {
+                *total += started.elapsed();
+            }
+
+            self.pending.insert(
+                command.id,
+                PendingCommand {
+                    kind: command.kind,
+                    issued_at: now,
+                },
+            );
+        }
+    }
+
+    fn record_failure(&mut self, error: String) {
+        self.failures += 1;
+        self.last_error = Some(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(commands: &mut Commands, id: u64, exit_code: i32) -> Option<CommandOutcome> {
+        let context = BTreeMap::from([(CONTEXT_ID.to_string(), id.to_string())]);
+        commands.handle_result(Some(exit_code), Vec::new(), b"", &context)
+    }
+
+    fn age(commands: &mut Commands, id: u64, secs: u64) {
+        let pending = commands
+            .pending
+            .get_mut(&id)
+            .or(commands.overdue.get_mut(&id));
+        pending.expect("in flight").issued_at -= secs;
+    }
+
+    #[test]
+    fn slow_command_frees_its_slot_and_still_reports() {
+        let mut commands = Commands::new(1);
+        commands.run(CommandKind::Upload, &["sleep".to_string()]);
+        commands.run(CommandKind::Export, &["true".to_string()]);
+        assert_eq!((commands.in_flight(), commands.queued()), (1, 1));
+
+        age(&mut commands, 0, SLOT_SECS);
+        commands.pump();
+        assert_eq!(commands.queued(), 1, "uploads get longer than 30s");
+
+        age(&mut commands, 0, USER_SLOT_SECS);
+        commands.pump();
+        assert_eq!(
+            (commands.in_flight(), commands.queued(), commands.overdue()),
+            (1, 0, 1)
+        );
+        assert_eq!(commands.failures, 0);
+
+        let outcome = result(&mut commands, 0, 0).expect("overdue result is routed");
+        assert_eq!(outcome.kind, CommandKind::Upload);
+        assert!(outcome.error.is_none());
+        assert_eq!((commands.overdue(), commands.failures), (0, 0));
+    }
+
+    #[test]
+    fn lost_commands_fail_unless_user_supplied() {
+        let mut commands = Commands::new(2);
+        commands.run(CommandKind::Heartbeat, &["true".to_string()]);
+        commands.run(CommandKind::Hook(HookEvent::Open), &["true".to_string()]);
+        age(&mut commands, 0, LOST_AFTER_SECS);
+        age(&mut commands, 1, LOST_AFTER_SECS);
+        commands.pump();
+        commands.pump();
+        assert_eq!(commands.failures, 1);
+        assert_eq!(commands.overdue(), 1);
+        assert!(result(&mut commands, 0, 0).is_none());
+        assert!(result(&mut commands, 1, 0).is_some());
+    }
+}
@@ -0,0 +1,901 @@
+use crate::capture::CaptureFormat;
+use crate::category::CategoryRule;
+use crate::export::{ExportFormat, PaneIdFormat};
+use crate::hooks::{HookEvent, Hooks};
+use crate::host::Shell;
+use crate::icons::IconMap;
+use crate::log::LogLevel;
+use crate::naming::{self, DEFAULT_NAME_EXCLUDE, DEFAULT_NAME_TEMPLATE};
+use crate::profiles::ContextProfile;
+use crate::quiet::{self, QuietHours};
+use crate::redact::Redactor;
+use crate::spawn::PaneTemplate;
+use crate::stats;
+use crate::template::RowTemplate;
+use crate::watch::CommandWatcher;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use zellij_tile::prelude::KeyWithModifier;
+
+pub const DEFAULT_LOG_FILE: &str = "/tmp/zj-pane-tracker.log";
+pub const DEFAULT_EXPORT_DIR: &str = "/tmp";
+pub const DEFAULT_MAX_CONCURRENT_COMMANDS: usize = 4;
+/// Where Zellij mounts the plugin's working directory inside the sandbox
+pub const HOST_DIR: &str = "/host";
+
+/// Plugin configuration, read from the plugin block in the layout or config.kdl
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Never request `RunCommands`: files are written straight to `/host`
+    /// and content capture is off
+    pub minimal_permissions: bool,
+    /// Write what would be run and written to `zj-dry-run.log` instead of
+    /// doing it
+    pub dry_run: bool,
+    /// Read and write files with `std::fs` under `/host` instead of through
+    /// `sh`, for hosts without a POSIX shell; host commands still capture
+    pub direct_writes: bool,
+    /// Measure how long each update cycle takes and export it in `stats`
+    pub benchmark: bool,
+    /// Minimum level written to the log file (`debug`, `info`, `warn`, `error`)
+    pub log_level: LogLevel,
+    /// Path of the plugin log file
+    pub log_file: String,
+    /// Settings file read at load and on `reload`, by default
+    /// `<export_dir>/zj-tracker.kdl`
+    pub config_file: Option<String>,
+    /// Directory the names JSON and pane captures are written to
+    pub export_dir: String,
+    /// Encoding of the names export (`json` or `msgpack`)
+    pub export_format: ExportFormat,
+    /// How pane ids are written in exports and hook events
+    pub pane_id_format: PaneIdFormat,
+    /// Seconds between captures of each pane's content, 0 disables capturing
+    pub capture_interval: u64,
+    /// Per-pane interval overrides, first match wins
+    pub capture_rules: Vec<IntervalRule>,
+    /// How panes are captured and stored: viewport or full scrollback,
+    /// plain or with ANSI sequences, optionally gzipped
+    pub capture_format: CaptureFormat,
+    /// Per-pane format overrides, first match wins
+    pub capture_format_rules: Vec<FormatRule>,
+    /// Largest multiplier applied to the interval of panes whose content
+    /// changes on every capture, 1 disables the backoff
+    pub capture_backoff_max: u64,
+    /// Commit the export directory to a git repository after every export
+    pub git_snapshots: bool,
+    /// Shell command run after every export cycle to copy the exports elsewhere
+    pub upload_cmd: Option<String>,
+    /// Name capture files after the pane's repo and branch when known
+    pub git_capture_names: bool,
+    /// Captures larger than this keep only their last `max_capture_bytes`
+    /// bytes, 0 keeps everything
+    pub max_capture_bytes: usize,
+    /// Captures started per minute across all panes, 0 for no limit
+    pub max_captures_per_minute: usize,
+    /// Capture bytes written per minute across all panes, 0 for no limit
+    pub max_capture_bytes_per_minute: usize,
+    /// Scrubs credentials from captures: the built-in rules unless `redact`
+    /// is off, then `redact_rules` and the rules file
+    pub redactor: Redactor,
+    /// Shell command that encrypts stdin to stdout (e.g. `age -r <key>`).
+    /// When set, every file with session contents is written through it.
+    pub encrypt_cmd: Option<String>,
+    /// Shell command that reverses `encrypt_cmd`, for reading the state file
+    /// back and previewing captures
+    pub decrypt_cmd: Option<String>,
+    /// Also write a box diagram of each tab's layout with every export
+    pub export_layout: bool,
+    /// Also write a Graphviz graph of the session with every export
+    pub export_dot: bool,
+    /// Also write `zj-panes.env` with a shell variable per named pane
+    pub export_env: bool,
+    /// Rewrite `zj-changed` with the changed pane ids after every export
+    /// cycle that changed something, for inotify watchers
+    pub change_marker: bool,
+    /// Seconds between heartbeat file writes, 0 disables the heartbeat
+    pub heartbeat_interval: u64,
+    /// Shell for host commands that need redirection or pipes, and for
+    /// hooks and other user-supplied command strings
+    pub shell: Shell,
+    /// Upper bound on host commands running at the same time
+    pub max_concurrent_commands: usize,
+    /// Shell command that receives copied content on stdin (e.g. `wl-copy`, `pbcopy`).
+    /// When unset, copies go through the OSC 52 escape sequence.
+    pub clipboard_cmd: Option<String>,
+    /// Number of trailing lines shown in the floating preview
+    pub preview_lines: usize,
+    /// Key (e.g. `Ctrl g`) that must precede action keys in the tracker
+    /// pane, so stray keypresses don't trigger anything
+    pub key_prefix: Option<KeyWithModifier>,
+    /// Start with suppressed and unselectable panes hidden from the list
+    pub hide_unselectable: bool,
+    /// Rules assigning panes to categories by command, first match wins
+    pub category_rules: Vec<CategoryRule>,
+    /// Format of each pane row in the list
+    pub row_template: RowTemplate,
+    /// Program -> icon map used in the list and exports
+    pub icons: IconMap,
+    /// Shell commands run on pane events (`on_pane_open` etc.)
+    pub hooks: Hooks,
+    /// Titles treated as Zellij defaults by auto-naming and suggestions
+    pub default_name_pattern: Regex,
+    /// Rename panes with default titles after their command
+    pub auto_name: bool,
+    /// Name format for `auto_name`: `{program}`, `{target}`, `{repo}`, `{branch}`
+    pub auto_name_template: String,
+    /// Programs whose panes are never auto-named
+    pub auto_name_exclude: Vec<String>,
+    /// MQTT topic prefix pane events are published under, unset disables publishing
+    pub mqtt_topic: Option<String>,
+    /// Command that publishes stdin as a message, given `-t <topic> -s`
+    pub mqtt_pub_cmd: String,
+    /// Daily windows during which hooks and MQTT events are suppressed
+    pub quiet_hours: QuietHours,
+    /// Minutes east of UTC that `quiet_hours` is written in
+    pub utc_offset: i32,
+    /// Lines of a capture counted as errors in the exported content stats
+    pub error_line_pattern: Regex,
+    /// Patterns for commands worth an `on_command` event and a line in
+    /// `zj-commands.jsonl`
+    pub command_watchers: Vec<CommandWatcher>,
+    /// Append how long each terminal pane had focus to `focus-timeline.csv`
+    pub focus_timeline: bool,
+    /// Named pane selections bundled with `C` or the `bundle` pipe command
+    pub context_profiles: Vec<ContextProfile>,
+    /// Panes that can be launched from the tracker
+    pub pane_templates: Vec<PaneTemplate>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            minimal_permissions: false,
+            dry_run: false,
+            direct_writes: false,
+            benchmark: false,
+            log_level: LogLevel::Warn,
+            log_file: DEFAULT_LOG_FILE.to_string(),
+            config_file: None,
+            export_dir: DEFAULT_EXPORT_DIR.to_string(),
+            export_format: ExportFormat::Json,
+            pane_id_format: PaneIdFormat::default(),
+            capture_interval: 0,
+            capture_rules: Vec::new(),
+            capture_format: CaptureFormat::default(),
+            capture_format_rules: Vec::new(),
+            capture_backoff_max: 8,
+            git_snapshots: false,
+            upload_cmd: None,
+            git_capture_names: false,
+            max_capture_bytes: 1024 * 1024,
+            max_captures_per_minute: 0,
+            max_capture_bytes_per_minute: 0,
+            redactor: Redactor::default(),
+            encrypt_cmd: None,
+            decrypt_cmd: None,
+            export_layout: false,
+            export_dot: false,
+            export_env: false,
+            change_marker: false,
+            heartbeat_interval: 30,
+            shell: Shell::default(),
+            max_concurrent_commands: DEFAULT_MAX_CONCURRENT_COMMANDS,
+            clipboard_cmd: None,
+            preview_lines: 40,
+            key_prefix: None,
+            hide_unselectable: false,
+            category_rules: CategoryRule::defaults(),
+            row_template: RowTemplate::default(),
+            icons: IconMap::default(),
+            hooks: Hooks::default(),
+            pane_templates: Vec::new(),
+            default_name_pattern: Regex::new(naming::DEFAULT_NAME_PATTERN)
+                .expect("built-in default name pattern"),
+            auto_name: false,
+            auto_name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            auto_name_exclude: DEFAULT_NAME_EXCLUDE.iter().map(|p| p.to_string()).collect(),
+            mqtt_topic: None,
+            mqtt_pub_cmd: "mosquitto_pub".to_string(),
+            error_line_pattern: Regex::new(stats::DEFAULT_ERROR_PATTERN)
+                .expect("built-in error line pattern"),
+            command_watchers: Vec::new(),
+            focus_timeline: false,
+            context_profiles: Vec::new(),
+            quiet_hours: QuietHours::default(),
+            utc_offset: 0,
+        }
+    }
+}
+
+/// Which pane field a matcher looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchField {
+    Name,
+    Command,
+}
+
+/// Regex applied to a pane's name or command, written as `name:<regex>` or `command:<regex>`
+#[derive(Debug, Clone)]
+pub struct PaneMatcher {
+    pub field: MatchField,
+    pub pattern: Regex,
+}
+
+impl PaneMatcher {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (field, pattern) = spec.split_once(':').ok_or_else(|| {
+            format!(
+                "'{}' should look like name:<regex> or command:<regex>",
+                spec
+            )
+        })?;
+        let field = match field.trim() {
+            "name" => MatchField::Name,
+            "command" | "cmd" => MatchField::Command,
+            other => return Err(format!("unknown match field '{}'", other)),
+        };
+        let pattern = Regex::new(pattern.trim()).map_err(|e| e.to_string())?;
+        Ok(PaneMatcher { field, pattern })
+    }
+
+    pub fn matches(&self, name: &str, command: Option<&str>) -> bool {
+        match self.field {
+            MatchField::Name => self.pattern.is_match(name),
+            MatchField::Command => command.is_some_and(|c| self.pattern.is_match(c)),
+        }
+    }
+}
+
+/// Capture format for panes matching a pattern, written as `<matcher>=<format>`
+#[derive(Debug, Clone)]
+pub struct FormatRule {
+    pub matcher: PaneMatcher,
+    pub format: CaptureFormat,
+}
+
+impl FormatRule {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (matcher, format) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| format!("'{}' is missing '=<format>'", spec))?;
+        Ok(FormatRule {
+            matcher: PaneMatcher::parse(matcher)?,
+            format: CaptureFormat::parse(format)?,
+        })
+    }
+}
+
+/// Capture interval for panes matching a pattern, written as `<matcher>=<seconds>`
+#[derive(Debug, Clone)]
+pub struct IntervalRule {
+    pub matcher: PaneMatcher,
+    pub interval: u64,
+}
+
+impl IntervalRule {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (matcher, interval) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| format!("'{}' is missing '=<seconds>'", spec))?;
+        let interval = interval
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| format!("invalid interval '{}'", interval.trim()))?;
+        Ok(IntervalRule {
+            matcher: PaneMatcher::parse(matcher)?,
+            interval,
+        })
+    }
+}
+
+impl Config {
+    /// Parse the configuration map, falling back to defaults for missing keys.
+    /// Invalid values are reported in the returned warnings instead of failing the load.
+    pub fn from_map(map: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut config = Config::default();
+        let mut warnings = Vec::new();
+
+        if let Some(value) = map.get("log_level") {
+            match LogLevel::parse(value) {
+                Some(level) => config.log_level = level,
+                None => warnings.push(format!("invalid log_level '{}', using warn", value)),
+            }
+        }
+        if let Some(value) = map.get("log_file") {
+            config.log_file = value.clone();
+        }
+        if let Some(value) = map.get("export_dir") {
+            config.export_dir = value.trim_end_matches('/').to_string();
+        }
+        if let Some(value) = map.get("config_file") {
+            config.config_file = Some(value.clone()).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(value) = map.get("export_format") {
+            match ExportFormat::parse(value) {
+                Some(format) => config.export_format = format,
+                None => warnings.push(format!("invalid export_format '{}', using json", value)),
+            }
+        }
+        if let Some(value) = map.get("pane_id_format") {
+            match PaneIdFormat::parse(value) {
+                Ok(format) => config.pane_id_format = format,
+                Err(e) => warnings.push(format!("invalid pane_id_format: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("capture_interval") {
+            match value.trim().parse::<u64>() {
+                Ok(secs) => config.capture_interval = secs,
+                Err(_) => warnings.push(format!("invalid capture_interval '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("capture_backoff_max") {
+            match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 => config.capture_backoff_max = n,
+                _ => warnings.push(format!("invalid capture_backoff_max '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("git_snapshots") {
+            match parse_bool(value) {
+                Some(flag) => config.git_snapshots = flag,
+                None => warnings.push(format!("invalid git_snapshots '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("upload_cmd") {
+            config.upload_cmd = Some(value.clone()).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(value) = map.get("git_capture_names") {
+            match parse_bool(value) {
+                Some(flag) => config.git_capture_names = flag,
+                None => warnings.push(format!("invalid git_capture_names '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("max_capture_bytes") {
+            match value.trim().parse::<usize>() {
+                Ok(n) => config.max_capture_bytes = n,
+                Err(_) => warnings.push(format!("invalid max_capture_bytes '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("max_captures_per_minute") {
+            match value.trim().parse::<usize>() {
+                Ok(n) => config.max_captures_per_minute = n,
+                Err(_) => warnings.push(format!("invalid max_captures_per_minute '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("max_capture_bytes_per_minute") {
+            match value.trim().parse::<usize>() {
+                Ok(n) => config.max_capture_bytes_per_minute = n,
+                Err(_) => {
+                    warnings.push(format!("invalid max_capture_bytes_per_minute '{}'", value))
+                }
+            }
+        }
+        if let Some(value) = map.get("redact") {
+            match parse_bool(value) {
+                Some(true) => {}
+                Some(false) => config.redactor = Redactor::empty(),
+                None => warnings.push(format!("invalid redact '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("redact_rules") {
+            let errors = config.redactor.add_rules(value.split(';'));
+            warnings.extend(errors.into_iter().map(|e| format!("redact_rules: {}", e)));
+        }
+        if let Some(value) = map.get("export_layout") {
+            match parse_bool(value) {
+                Some(flag) => config.export_layout = flag,
+                None => warnings.push(format!("invalid export_layout '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("export_dot") {
+            match parse_bool(value) {
+                Some(flag) => config.export_dot = flag,
+                None => warnings.push(format!("invalid export_dot '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("export_env") {
+            match parse_bool(value) {
+                Some(flag) => config.export_env = flag,
+                None => warnings.push(format!("invalid export_env '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("change_marker") {
+            match parse_bool(value) {
+                Some(flag) => config.change_marker = flag,
+                None => warnings.push(format!("invalid change_marker '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("heartbeat_interval") {
+            match value.trim().parse::<u64>() {
+                Ok(secs) => config.heartbeat_interval = secs,
+                Err(_) => warnings.push(format!("invalid heartbeat_interval '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("shell") {
+            match Shell::parse(value) {
+                Some(shell) => config.shell = shell,
+                None => warnings.push("empty shell, using sh".to_string()),
+            }
+        }
+        if let Some(value) = map.get("max_concurrent_commands") {
+            match value.trim().parse::<usize>() {
+                Ok(n) if n > 0 => config.max_concurrent_commands = n,
+                _ => warnings.push(format!("invalid max_concurrent_commands '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("clipboard_cmd") {
+            config.clipboard_cmd = Some(value.clone()).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(value) = map.get("encrypt_cmd") {
+            config.encrypt_cmd = Some(value.clone()).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(value) = map.get("decrypt_cmd") {
+            config.decrypt_cmd = Some(value.clone()).filter(|v| !v.trim().is_empty());
+        }
+        if let Some(value) = map.get("preview_lines") {
+            match value.trim().parse::<usize>() {
+                Ok(n) if n > 0 => config.preview_lines = n,
+                _ => warnings.push(format!("invalid preview_lines '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("key_prefix") {
+            match KeyWithModifier::from_str(value.trim()) {
+                Ok(key) => config.key_prefix = Some(key),
+                Err(_) => warnings.push(format!("invalid key_prefix '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("hide_unselectable") {
+            match parse_bool(value) {
+                Some(flag) => config.hide_unselectable = flag,
+                None => warnings.push(format!("invalid hide_unselectable '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("category_rules") {
+            let (rules, errors) = CategoryRule::parse_list(value);
+            config.category_rules = rules;
+            warnings.extend(errors.into_iter().map(|e| format!("category_rules: {}", e)));
+        }
+        if let Some(value) = map.get("row_template") {
+            match RowTemplate::parse(value) {
+                Ok(template) => config.row_template = template,
+                Err(e) => warnings.push(format!("row_template: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("icons") {
+            let errors = config.icons.apply_overrides(value);
+            warnings.extend(errors.into_iter().map(|e| format!("icons: {}", e)));
+        }
+        if let Some(value) = map.get("capture_rules") {
+            for spec in value.split(';').filter(|s| !s.trim().is_empty()) {
+                match IntervalRule::parse(spec.trim()) {
+                    Ok(rule) => config.capture_rules.push(rule),
+                    Err(e) => warnings.push(format!("capture_rules: {}", e)),
+                }
+            }
+        }
+        if let Some(value) = map.get("capture_format") {
+            match CaptureFormat::parse(value) {
+                Ok(format) => config.capture_format = format,
+                Err(e) => warnings.push(format!("capture_format: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("capture_format_rules") {
+            for spec in value.split(';').filter(|s| !s.trim().is_empty()) {
+                match FormatRule::parse(spec.trim()) {
+                    Ok(rule) => config.capture_format_rules.push(rule),
+                    Err(e) => warnings.push(format!("capture_format_rules: {}", e)),
+                }
+            }
+        }
+        if let Some(value) = map.get("pane_templates") {
+            let (templates, errors) = PaneTemplate::parse_list(value);
+            config.pane_templates = templates;
+            warnings.extend(errors.into_iter().map(|e| format!("pane_templates: {}", e)));
+        }
+        for event in HookEvent::ALL {
+            if let Some(value) = map.get(event.config_key()) {
+                config.hooks.set(event, value);
+            }
+        }
+        if let Some(value) = map.get("default_name_pattern") {
+            match Regex::new(value.trim()) {
+                Ok(pattern) => config.default_name_pattern = pattern,
+                Err(e) => warnings.push(format!("default_name_pattern: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("auto_name") {
+            match parse_bool(value) {
+                Some(flag) => config.auto_name = flag,
+                None => warnings.push(format!("invalid auto_name '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("auto_name_template") {
+            match naming::validate_template(value) {
+                Ok(()) => config.auto_name_template = value.clone(),
+                Err(e) => warnings.push(format!("auto_name_template: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("auto_name_exclude") {
+            config.auto_name_exclude = value
+                .split(';')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(value) = map.get("mqtt_topic") {
+            let topic = value.trim().trim_end_matches('/');
+            config.mqtt_topic = Some(topic.to_string()).filter(|t| !t.is_empty());
+        }
+        if let Some(value) = map.get("mqtt_pub_cmd") {
+            if !value.trim().is_empty() {
+                config.mqtt_pub_cmd = value.clone();
+            }
+        }
+        if let Some(value) = map.get("error_line_pattern") {
+            match Regex::new(value.trim()) {
+                Ok(pattern) => config.error_line_pattern = pattern,
+                Err(e) => warnings.push(format!("error_line_pattern: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("command_watchers") {
+            for spec in value.split(';').filter(|s| !s.trim().is_empty()) {
+                match CommandWatcher::parse(spec.trim()) {
+                    Ok(watcher) => config.command_watchers.push(watcher),
+                    Err(e) => warnings.push(format!("command_watchers: {}", e)),
+                }
+            }
+        }
+        if let Some(value) = map.get("focus_timeline") {
+            match parse_bool(value) {
+                Some(flag) => config.focus_timeline = flag,
+                None => warnings.push(format!("invalid focus_timeline '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("context_profiles") {
+            for spec in value.split(';').filter(|s| !s.trim().is_empty()) {
+                match ContextProfile::parse(spec.trim()) {
+                    Ok(profile) => config.context_profiles.push(profile),
+                    Err(e) => warnings.push(format!("context_profiles: {}", e)),
+                }
+            }
+        }
+        if let Some(value) = map.get("quiet_hours") {
+            match QuietHours::parse(value) {
+                Ok(quiet_hours) => config.quiet_hours = quiet_hours,
+                Err(e) => warnings.push(format!("quiet_hours: {}", e)),
+            }
+        }
+        if let Some(value) = map.get("utc_offset") {
+            match quiet::parse_utc_offset(value.trim()) {
+                Some(offset) => config.utc_offset = offset,
+                None => warnings.push(format!("invalid utc_offset '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("minimal_permissions") {
+            match parse_bool(value) {
+                Some(flag) => config.minimal_permissions = flag,
+                None => warnings.push(format!("invalid minimal_permissions '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("benchmark") {
+            match parse_bool(value) {
+                Some(flag) => config.benchmark = flag,
+                None => warnings.push(format!("invalid benchmark '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("dry_run") {
+            match parse_bool(value) {
+                Some(flag) => config.dry_run = flag,
+                None => warnings.push(format!("invalid dry_run '{}'", value)),
+            }
+        }
+        if let Some(value) = map.get("direct_writes") {
+            match parse_bool(value) {
+                Some(flag) => config.direct_writes = flag,
+                None => warnings.push(format!("invalid direct_writes '{}'", value)),
+            }
+        }
+        if config.direct_writes && !config.minimal_permissions {
+            config.apply_direct_writes(&mut warnings);
+        }
+        if config.git_snapshots && config.export_dir == DEFAULT_EXPORT_DIR {
+            warnings.push(
+                "git_snapshots: committing in /tmp, set export_dir to a dedicated directory"
+                    .to_string(),
+            );
+        }
+        if config.minimal_permissions {
+            config.apply_minimal_permissions(&mut warnings);
+        }
+
+        (config, warnings)
+    }
+
+    /// Turn off everything that needs host commands and point the output
+    /// files at the plugin's `/host` directory
+    fn apply_minimal_permissions(&mut self, warnings: &mut Vec<String>) {
+        if self.export_dir != DEFAULT_EXPORT_DIR {
+            warnings.push(format!(
+                "minimal_permissions: export_dir '{}' ignored, writing to the plugin's cwd",
+                self.export_dir
+            ));
+        }
+        if self.capture_enabled() {
+            warnings.push("minimal_permissions: content capture disabled".to_string());
+        }
+        if self.clipboard_cmd.is_some()
+            || self.encrypt_cmd.is_some()
+            || !self.hooks.is_empty()
+            || self.git_snapshots
+            || self.upload_cmd.is_some()
+            || self.mqtt_topic.is_some()
+        {
+            warnings.push(
+                "minimal_permissions: clipboard_cmd, encrypt_cmd, hooks, git_snapshots, upload_cmd and mqtt_topic are ignored"
+                    .to_string(),
+            );
+        }
+        self.export_dir = HOST_DIR.to_string();
+        let log_name = self.log_file.rsplit('/').next().unwrap_or_default();
+        self.log_file = format!("{}/{}", HOST_DIR, log_name);
+        self.capture_interval = 0;
+        self.capture_rules.clear();
+        self.clipboard_cmd = None;
+        self.encrypt_cmd = None;
+        self.decrypt_cmd = None;
+        self.hooks.clear();
+        self.git_snapshots = false;
+        self.upload_cmd = None;
+        self.mqtt_topic = None;
+    }
+
+    /// Default the output files to `/host`, the plugin's cwd on the host,
+    /// since that's where the sandbox can write whatever the host's OS.
+    /// Paths the user chose outside it can only be reached through the
+    /// shell, so they keep it.
+    fn apply_direct_writes(&mut self, warnings: &mut Vec<String>) {
+        let outside = |path: &str| !Path::new(path).starts_with(HOST_DIR);
+        let shell_needed = if self.encrypt_cmd.is_some() {
+            Some("encrypt_cmd needs the shell".to_string())
+        } else if self.export_dir != DEFAULT_EXPORT_DIR && outside(&self.export_dir) {
+            Some(format!(
+                "export_dir '{}' is outside {}",
+                self.export_dir, HOST_DIR
+            ))
+        } else if self.log_file != DEFAULT_LOG_FILE && outside(&self.log_file) {
+            Some(format!(
+                "log_file '{}' is outside {}",
+                self.log_file, HOST_DIR
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = shell_needed {
+            warnings.push(format!(
+                "direct_writes: {}, writing through the shell instead",
+                reason
+            ));
+            self.direct_writes = false;
+            return;
+        }
+        if outside(&self.export_dir) {
+            self.export_dir = HOST_DIR.to_string();
+        }
+        if outside(&self.log_file) {
+            let log_name = Path::new(&self.log_file)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.log_file = self.export_file(log_name);
+        }
+        // Compressing needs gzip on the host
+        let gzipped = self.capture_format.gzip
+            || self
+                .capture_format_rules
+                .iter()
+                .any(|rule| rule.format.gzip);
+        if gzipped {
+            warnings.push("direct_writes: captures are written uncompressed".to_string());
+            self.capture_format.gzip = false;
+            for rule in &mut self.capture_format_rules {
+                rule.format.gzip = false;
+            }
+        }
+    }
+
+    /// Whether files are read and written in the sandbox rather than by
+    /// host commands
+    pub fn direct_io(&self) -> bool {
+        self.minimal_permissions || self.direct_writes
+    }
+
+    /// Path of the file `name` in the export directory
+    pub fn export_file(&self, name: impl AsRef<Path>) -> String {
+        Path::new(&self.export_dir)
+            .join(name)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn names_path(&self) -> String {
+        self.export_file(format!("zj-pane-names.{}", self.export_format.extension()))
+    }
+
+    pub fn marker_path(&self) -> String {
+        self.export_file("zj-changed")
+    }
+
+    pub fn schema_path(&self) -> String {
+        self.export_file("zj-pane-names.schema.json")
+    }
+
+    pub fn changes_path(&self) -> String {
+        self.export_file("zj-changes.json")
+    }
+
+    pub fn layout_path(&self) -> String {
+        self.export_file("zj-layout.txt")
+    }
+
+    pub fn dot_path(&self) -> String {
+        self.export_file("zj-session.dot")
+    }
+
+    pub fn bundle_path(&self) -> String {
+        self.export_file("zj-bundle.txt")
+    }
+
+    pub fn profile_bundle_path(&self, profile: &str) -> String {
+        self.export_file(format!("zj-bundle-{}.txt", profile))
+    }
+
+    pub fn env_path(&self) -> String {
+        self.export_file("zj-panes.env")
+    }
+
+    pub fn config_file_path(&self) -> String {
+        self.config_file
+            .clone()
+            .unwrap_or_else(|| self.export_file("zj-tracker.kdl"))
+    }
+
+    pub fn state_path(&self) -> String {
+        self.export_file("zj-tracker-state.json")
+    }
+
+    pub fn heartbeat_path(&self) -> String {
+        self.export_file("zj-tracker-heartbeat.json")
+    }
+
+    /// Optional file of extra redaction rules, one per line
+    pub fn redact_rules_path(&self) -> String {
+        self.export_file("zj-redact-rules")
+    }
+
+    /// Readable log of failed panes and watched commands, followed by the
+    /// alerts pane
+    pub fn alerts_log_path(&self) -> String {
+        self.export_file("zj-alerts.log")
+    }
+
+    /// Time spent focused on each terminal pane, as CSV
+    pub fn focus_timeline_path(&self) -> String {
+        self.export_file("focus-timeline.csv")
+    }
+
+    /// Preview log of the commands and writes `dry_run` held back
+    pub fn dry_run_path(&self) -> String {
+        self.export_file("zj-dry-run.log")
+    }
+
+    /// Log of watched commands panes started, one JSON object per line
+    pub fn commands_log_path(&self) -> String {
+        self.export_file("zj-commands.jsonl")
+    }
+
+    /// File a terminal pane's content is captured to
+    pub fn capture_path(&self, terminal_id: u32) -> String {
+        self.export_file(format!("zj-pane-{}.txt", terminal_id))
+    }
+
+    /// Capture file including a `repo-branch` slug, e.g. `zj-myrepo-main-terminal_3.txt`
+    pub fn named_capture_path(&self, terminal_id: u32, slug: &str) -> String {
+        self.export_file(format!("zj-{}-terminal_{}.txt", slug, terminal_id))
+    }
+
+    /// Capture interval for a pane, taking per-pane rules into account
+    pub fn capture_interval_for(&self, name: &str, command: Option<&str>) -> u64 {
+        self.capture_rules
+            .iter()
+            .find(|rule| rule.matcher.matches(name, command))
+            .map(|rule| rule.interval)
+            .unwrap_or(self.capture_interval)
+    }
+
+    /// Capture format for a pane, taking per-pane rules into account
+    pub fn capture_format_for(&self, name: &str, command: Option<&str>) -> CaptureFormat {
+        self.capture_format_rules
+            .iter()
+            .find(|rule| rule.matcher.matches(name, command))
+            .map(|rule| rule.format)
+            .unwrap_or(self.capture_format)
+    }
+
+    pub fn capture_enabled(&self) -> bool {
+        self.capture_interval > 0 || self.capture_rules.iter().any(|r| r.interval > 0)
+    }
+
+    /// Whether captures or the heartbeat need the timer running
+    pub fn timer_needed(&self) -> bool {
+        self.capture_enabled() || self.heartbeat_interval > 0
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(settings: &[(&str, &str)]) -> (Config, Vec<String>) {
+        let map: BTreeMap<String, String> = settings
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Config::from_map(&map)
+    }
+
+    #[test]
+    fn direct_writes_go_to_the_host_dir() {
+        let (config, warnings) = parse(&[("direct_writes", "true")]);
+        assert!(warnings.is_empty());
+        assert!(config.direct_io());
+        assert_eq!(config.names_path(), "/host/zj-pane-names.json");
+        assert_eq!(config.log_file, "/host/zj-pane-tracker.log");
+
+        let (config, warnings) = parse(&[("direct_writes", "true"), ("export_dir", "/host/zj/")]);
+        assert!(warnings.is_empty());
+        assert_eq!(config.capture_path(3), "/host/zj/zj-pane-3.txt");
+
+        let (config, warnings) =
+            parse(&[("direct_writes", "true"), ("capture_format", "full,gzip")]);
+        assert!(!config.capture_format.gzip);
+        assert_eq!(
+            warnings,
+            ["direct_writes: captures are written uncompressed"]
+        );
+    }
+
+    #[test]
+    fn chosen_dirs_outside_host_keep_the_shell() {
+        let (config, warnings) = parse(&[("direct_writes", "true"), ("export_dir", "/hostile")]);
+        assert!(!config.direct_io());
+        assert_eq!(config.export_dir, "/hostile");
+        assert_eq!(
+            warnings,
+            ["direct_writes: export_dir '/hostile' is outside /host, writing through the shell instead"]
+        );
+
+        let (config, warnings) =
+            parse(&[("direct_writes", "true"), ("log_file", "/var/log/zj.log")]);
+        assert!(!config.direct_io());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn encryption_keeps_writes_in_the_shell() {
+        let (config, warnings) = parse(&[("direct_writes", "true"), ("encrypt_cmd", "age -r key")]);
+        assert!(!config.direct_io());
+        assert_eq!(config.export_dir, DEFAULT_EXPORT_DIR);
+        assert_eq!(warnings.len(), 1);
+    }
+}
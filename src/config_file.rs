@@ -0,0 +1,135 @@
+use crate::commands::{CommandKind, CommandOutcome};
+use crate::config::Config;
+use crate::pipe::split_args;
+use crate::{host, State};
+use std::collections::BTreeMap;
+
+/// Keys that only the plugin block can set: the permissions are requested
+/// before the file is read, and the file can't move itself
+const LAYOUT_ONLY: &[&str] = &["minimal_permissions", "config_file"];
+
+/// Read the config file's settings: one `key "value"` node per line with the
+/// same keys as the plugin block, `//` starting a comment line. This is the
+/// subset of KDL the plugin block itself uses.
+pub fn parse(text: &str) -> (BTreeMap<String, String>, Vec<String>) {
+    let mut map = BTreeMap::new();
+    let mut warnings = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        match split_args(line).as_deref() {
+            Ok([key, value]) => {
+                map.insert(key.clone(), value.clone());
+            }
+            Ok(_) => warnings.push(format!("line {}: expected `key \"value\"`", number + 1)),
+            Err(e) => warnings.push(format!("line {}: {}", number + 1, e)),
+        }
+    }
+    (map, warnings)
+}
+
+impl State {
+    /// Read the config file, applying it when the contents arrive
+    pub(crate) fn read_config_file(&mut self) {
+        let path = self.config.config_file_path();
+        if self.config.direct_io() {
+            let text = std::fs::read_to_string(&path).unwrap_or_default();
+            self.handle_command_outcome(CommandOutcome {
+                kind: CommandKind::ReadConfigFile,
+                stdout: text.into_bytes(),
+                error: None,
+            });
+        } else {
+            self.run_latest(CommandKind::ReadConfigFile, &host::read_file_cmd(&path));
+        }
+    }
+
+    /// Rebuild the configuration from the config file overlaid by the plugin
+    /// block, which wins where both set a key. What `load` did with the old
+    /// settings is redone where it depends on them: `hide_unselectable`, the
+    /// files read from `export_dir` and the timer.
+    pub(crate) fn apply_config_file(&mut self, text: &str) {
+        let path = self.config.config_file_path();
+        let (mut map, mut warnings) = parse(text);
+        if map.is_empty() && warnings.is_empty() && !self.config_from_file {
+            return;
+        }
+        for key in LAYOUT_ONLY {
+            if map.remove(*key).is_some() {
+                warnings.push(format!("{} can only be set in the plugin block", key));
+            }
+        }
+        self.config_from_file = !map.is_empty();
+        map.extend(self.layout_config.clone());
+
+        let (mut config, config_warnings) = Config::from_map(&map);
+        let moved = config.export_dir != self.config.export_dir;
+        if moved {
+            // The new directory's rules file replaces the old one's
+            self.redact_rules_text.clear();
+            self.state_read = false;
+        } else {
+            // Already reported when the rules file was read
+            let _ = config.redactor.add_rules_file(&self.redact_rules_text);
+        }
+        if config.hide_unselectable != self.config.hide_unselectable {
+            self.hide_unselectable = config.hide_unselectable;
+        }
+        let had_timer = self.config.timer_needed();
+        self.log.reconfigure(config.log_level, &config.log_file);
+        self.commands
+            .set_max_in_flight(config.max_concurrent_commands);
+        self.commands.dry_run = config.dry_run;
+        if !config.benchmark {
+            self.stats.benchmark = None;
+        }
+        self.config = config;
+        for warning in warnings {
+            self.log.warn(format!("{}: {}", path, warning));
+        }
+        for warning in config_warnings {
+            self.log.warn(format!("config: {}", warning));
+        }
+        if moved {
+            self.read_export_dir_files();
+        }
+        // A running timer picks the new intervals up when it next fires
+        if !had_timer {
+            self.schedule_tick();
+        }
+        self.log.info(format!("applied settings from {}", path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read_one_per_line() {
+        let (map, warnings) = parse(
+            "// shared settings\n\ncapture_interval \"5\"\n  quiet_hours \"22:00-07:00\"\nicons \"vim=✏\"\nlog_level \"debug\" // no trailing comments\nexport_dir \"/tmp/a\"\nexport_dir \"/tmp/b\"\n",
+        );
+        assert_eq!(map["capture_interval"], "5");
+        assert_eq!(map["quiet_hours"], "22:00-07:00");
+        assert_eq!(map["icons"], "vim=✏");
+        assert_eq!(map["export_dir"], "/tmp/b");
+        assert!(!map.contains_key("log_level"));
+        assert_eq!(warnings, vec!["line 6: expected `key \"value\"`"]);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported_by_number() {
+        let (map, warnings) = parse("dry_run\nencrypt_cmd \"age -r\ncapture_interval \"5\"\n");
+        assert_eq!(map.len(), 1);
+        assert_eq!(
+            warnings,
+            vec![
+                "line 1: expected `key \"value\"`",
+                "line 2: unterminated double quote"
+            ]
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{terminal_id, TrackedPane};
+
+/// What one pane manifest changed compared to the previous one
+#[derive(Default)]
+pub struct ManifestDiff {
+    /// Ids of panes that weren't in the previous manifest
+    pub added: Vec<String>,
+    /// Panes that are gone, as they were last seen
+    pub removed: Vec<(String, TrackedPane)>,
+    /// Ids of panes whose title changed, with the previous title
+    pub renamed: Vec<(String, String)>,
+    /// Ids of command panes that just exited with a non-zero status
+    pub failed: Vec<String>,
+    /// Ids of panes that started a command matching a `command_watchers`
+    /// entry, with the watcher's name
+    pub commands: Vec<(String, String)>,
+}
+
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.failed.is_empty()
+            && self.commands.is_empty()
+    }
+
+    /// Terminal ids of the removed panes, whose per-pane state can be dropped
+    pub fn removed_terminals(&self) -> impl Iterator<Item = u32> + '_ {
+        self.removed.iter().filter_map(|(id, _)| terminal_id(id))
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{terminal_id, State};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Quote a string for use as a DOT id or label
+fn quote(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Graphviz description of the session: the session node, a node per tab
+/// inside a cluster holding its panes (labelled with name and command),
+/// dashed edges from the pane that spawned a pane, and dotted edges to
+/// category nodes
+pub fn render_dot(state: &State) -> String {
+    let session = state.session_name.as_deref().unwrap_or("session");
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph zellij {{");
+    let _ = writeln!(out, "  rankdir=LR;");
+    let _ = writeln!(out, "  node [shape=box, fontname=\"monospace\"];");
+    let _ = writeln!(
+        out,
+        "  session [label={}, shape=doubleoctagon];",
+        quote(session)
+    );
+
+    let tabs: BTreeSet<usize> = state.panes.values().map(|pane| pane.tab).collect();
+    for tab in &tabs {
+        let tab_node = format!("tab{}", tab + 1);
+        let _ = writeln!(out, "  subgraph cluster_{} {{", tab_node);
+        let _ = writeln!(out, "    label={};", quote(&format!("Tab {}", tab + 1)));
+        let _ = writeln!(
+            out,
+            "    {} [label={}, shape=folder];",
+            tab_node,
+            quote(&format!("Tab {}", tab + 1))
+        );
+        for (pane_id, pane) in state.panes.iter().filter(|(_, p)| p.tab == *tab) {
+            let mut label = pane.info.title.clone();
+            if let Some(ref command) = pane.info.terminal_command {
+                label.push('\n');
+                label.push_str(command);
+            }
+            let style = if pane.info.is_plugin {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "    {} [label={}{}];",
+                quote(pane_id),
+                quote(&label),
+                style
+            );
+        }
+        let _ = writeln!(out, "  }}");
+    }
+
+    let categories: BTreeSet<&str> = state
+        .panes
+        .values()
+        .filter_map(|pane| pane.category.as_deref())
+        .collect();
+    for category in categories {
+        let _ = writeln!(
+            out,
+            "  {} [label={}, shape=ellipse];",
+            quote(&format!("category:{}", category)),
+            quote(category)
+        );
+    }
+
+    for tab in &tabs {
+        let _ = writeln!(out, "  session -> tab{};", tab + 1);
+    }
+    for (pane_id, pane) in &state.panes {
+        let _ = writeln!(out, "  tab{} -> {};", pane.tab + 1, quote(pane_id));
+        // A closed parent would show up as a node of its own
+        let parent = terminal_id(pane_id)
+            .and_then(|id| state.spawned_by.get(&id))
+            .filter(|parent| state.panes.contains_key(*parent));
+        if let Some(parent) = parent {
+            let _ = writeln!(
+                out,
+                "  {} -> {} [style=dashed, label=\"spawned\"];",
+                quote(parent),
+                quote(pane_id)
+            );
+        }
+        if let Some(ref category) = pane.category {
+            let _ = writeln!(
+                out,
+                "  {} -> {} [style=dotted];",
+                quote(pane_id),
+                quote(&format!("category:{}", category))
+            );
+        }
+    }
+    let _ = writeln!(out, "}}");
+    out
+}
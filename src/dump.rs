@@ -0,0 +1,141 @@
+use crate::commands::CommandKind;
+use crate::{capture, unix_now, State};
+use zellij_tile::prelude::*;
+
+/// Dumps that haven't finished after this long are answered with an error,
+/// so the waiting `zellij pipe` doesn't hang forever
+const DUMP_TIMEOUT_SECS: u64 = 30;
+
+/// A `dump` pipe request waiting for its capture (and write) to finish
+pub struct PendingDump {
+    /// CLI pipe to answer, unset for messages from other plugins
+    pub pipe_id: Option<String>,
+    pub terminal_id: u32,
+    /// File to write the content to; the content is the reply when unset
+    pub out: Option<String>,
+    pub started: u64,
+    /// Size of the content being written to `out`
+    pub bytes: usize,
+}
+
+impl State {
+    /// Capture a pane now, independently of the capture cycle, and answer
+    /// the pipe once the content is on disk. The CLI pipe is held open until
+    /// then.
+    pub(crate) fn start_dump(
+        &mut self,
+        target: &str,
+        out: Option<String>,
+        pipe_id: Option<String>,
+    ) -> Result<(), String> {
+        if self.config.minimal_permissions {
+            return Err(
+                "dumping needs RunCommands, which minimal_permissions leaves out".to_string(),
+            );
+        }
+        let terminal_id = self.resolve_terminal(target)?;
+        let id = self.next_dump;
+        self.next_dump += 1;
+        if let Some(ref pipe_id) = pipe_id {
+            block_cli_pipe_input(pipe_id);
+        }
+        self.pending_dumps.insert(
+            id,
+            PendingDump {
+                pipe_id,
+                terminal_id,
+                out,
+                started: unix_now(),
+                bytes: 0,
+            },
+        );
+        let argv = capture::capture_cmd(terminal_id, self.capture_format(terminal_id));
+        self.commands.run(CommandKind::Dump(id), &argv);
+        Ok(())
+    }
+
+    /// The pane's content arrived: write it out, or reply with it
+    pub(crate) fn dump_captured(&mut self, id: u64, stdout: &[u8]) {
+        let Some((terminal_id, out)) = self
+            .pending_dumps
+            .get(&id)
+            .map(|dump| (dump.terminal_id, dump.out.clone()))
+        else {
+            return;
+        };
+        let raw = String::from_utf8_lossy(stdout);
+        let raw = if self.capture_format(terminal_id).ansi {
+            raw
+        } else {
+            capture::strip_ansi(&raw).into_owned().into()
+        };
+        let content = self.config.redactor.redact(&raw).into_owned();
+        match out {
+            Some(path) => {
+                if let Some(dump) = self.pending_dumps.get_mut(&id) {
+                    dump.bytes = content.len();
+                }
+                self.write_file(CommandKind::DumpWrite(id), &path, &content);
+                // The write was only logged and won't report back
+                if self.commands.dry_run {
+                    let message =
+                        format!("dry run: would write {} bytes to {}", content.len(), path);
+                    self.finish_dump(id, Ok(message));
+                }
+            }
+            None => self.finish_dump(id, Ok(content)),
+        }
+    }
+
+    /// The dump's file was written
+    pub(crate) fn dump_written(&mut self, id: u64) {
+        let message = match self.pending_dumps.get(&id) {
+            Some(dump) => format!(
+                "dumped {} bytes of terminal_{} to {}",
+                dump.bytes,
+                dump.terminal_id,
+                dump.out.as_deref().unwrap_or_default()
+            ),
+            None => return,
+        };
+        self.finish_dump(id, Ok(message));
+    }
+
+    /// Answer the pipe that asked for the dump and forget it
+    pub(crate) fn finish_dump(&mut self, id: u64, result: Result<String, String>) {
+        let Some(dump) = self.pending_dumps.remove(&id) else {
+            return;
+        };
+        let text = match result {
+            Ok(text) => {
+                self.log
+                    .info(format!("dump terminal_{}: done", dump.terminal_id));
+                text
+            }
+            Err(e) => {
+                self.log
+                    .warn(format!("dump terminal_{}: {}", dump.terminal_id, e));
+                format!("error: {}", e)
+            }
+        };
+        if let Some(pipe_id) = dump.pipe_id {
+            let newline = if text.ends_with('\n') { "" } else { "\n" };
+            cli_pipe_output(&pipe_id, &format!("{}{}", text, newline));
+            unblock_cli_pipe_input(&pipe_id);
+        }
+    }
+
+    /// Fail dumps whose commands never reported back
+    pub(crate) fn expire_dumps(&mut self) {
+        let now = unix_now();
+        let expired: Vec<u64> = self
+            .pending_dumps
+            .iter()
+            .filter(|(_, dump)| now.saturating_sub(dump.started) >= DUMP_TIMEOUT_SECS)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            self.finish_dump(id, Err(format!("no result after {}s", DUMP_TIMEOUT_SECS)));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::host::shell_quote;
+use crate::naming::is_default_name;
+use crate::{terminal_id, State};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Variable name for a pane name: `ZJ_PANE_` and the name upper-cased,
+/// with runs of anything but letters and digits turned into one `_`
+fn var_name(name: &str) -> Option<String> {
+    let mut var = String::from("ZJ_PANE_");
+    let mut pending_underscore = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if pending_underscore && !var.ends_with('_') {
+                var.push('_');
+            }
+            pending_underscore = false;
+            var.push(c.to_ascii_uppercase());
+        } else {
+            pending_underscore = true;
+        }
+    }
+    (var.len() > "ZJ_PANE_".len()).then_some(var)
+}
+
+/// `zj-panes.env`: `ZJ_PANE_<NAME>=<pane id>` for every named terminal pane,
+/// plus `ZJ_PANE_<NAME>_FILE=<capture file>` once the pane has been
+/// captured. Panes still using their default title are left out, and a
+/// name taken by an earlier pane gets a `_2`, `_3`... suffix.
+pub fn render_env(state: &State) -> String {
+    let mut out = String::from("# Written by zellij-pane-tracker, source it from a shell\n");
+    let mut taken = BTreeSet::new();
+    let mut panes: Vec<(u32, &String)> = state
+        .panes
+        .iter()
+        .filter_map(|(pane_id, pane)| {
+            let id = terminal_id(pane_id)?;
+            if is_default_name(&state.config.default_name_pattern, &pane.info.title) {
+                return None;
+            }
+            Some((id, &pane.info.title))
+        })
+        .collect();
+    panes.sort_by_key(|(id, _)| *id);
+    for (id, name) in panes {
+        let Some(base) = var_name(name) else {
+            continue;
+        };
+        let var = (1..)
+            .map(|n| {
+                if n == 1 {
+                    base.clone()
+                } else {
+                    format!("{}_{}", base, n)
+                }
+            })
+            .find(|var| !taken.contains(var))
+            .unwrap_or(base);
+        let pane_id = state
+            .config
+            .pane_id_format
+            .apply(&format!("terminal_{}", id));
+        let _ = writeln!(out, "{}={}", var, shell_quote(&pane_id));
+        if state.pane_content.contains_key(&id) {
+            let _ = writeln!(
+                out,
+                "{}_FILE={}",
+                var,
+                shell_quote(&state.host_path(&state.capture_path(id)))
+            );
+        }
+        taken.insert(var);
+    }
+    out
+}
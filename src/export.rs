@@ -0,0 +1,471 @@
+use crate::git::GitInfo;
+use crate::identity;
+use crate::layout::{self, StackMembership};
+use crate::remote::ContainerRef;
+use crate::stats::{ContentStats, Stats};
+use crate::{State, TrackedPane};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use zellij_tile::prelude::PaneInfo;
+
+/// Encoding of the names export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Pretty-printed JSON, `zj-pane-names.json`
+    Json,
+    /// MessagePack with named fields, `zj-pane-names.msgpack`. Much cheaper
+    /// to produce than pretty JSON in sessions with hundreds of panes.
+    Msgpack,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json),
+            "msgpack" | "messagepack" => Some(ExportFormat::Msgpack),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Msgpack => "msgpack",
+        }
+    }
+}
+
+/// How pane ids are written in the exports and hook events: a template with
+/// `{kind}` (`terminal` or `plugin`), `{k}` (`t` or `p`) and `{id}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneIdFormat {
+    template: String,
+}
+
+impl Default for PaneIdFormat {
+    fn default() -> Self {
+        PaneIdFormat {
+            template: "{kind}_{id}".to_string(),
+        }
+    }
+}
+
+impl PaneIdFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let template = value.trim();
+        if !template.contains("{id}") {
+            return Err(format!("'{}' has no {{id}}", template));
+        }
+        Ok(PaneIdFormat {
+            template: template.to_string(),
+        })
+    }
+
+    /// Exported form of an internal `terminal_N` / `plugin_N` id. Plugin
+    /// panes keep the internal form when the template can't tell them apart
+    /// from terminals, since their numbers overlap.
+    pub fn apply(&self, pane_id: &str) -> String {
+        let Some((kind, id)) = pane_id.split_once('_') else {
+            return pane_id.to_string();
+        };
+        let distinguishes = self.template.contains("{kind}") || self.template.contains("{k}");
+        if kind == "plugin" && !distinguishes {
+            return pane_id.to_string();
+        }
+        self.template
+            .replace("{kind}", kind)
+            .replace("{k}", &kind[..1])
+            .replace("{id}", id)
+    }
+}
+
+/// JSON Schema of `PaneNamesExport`, written next to the export. Keep it in
+/// step with the structs below.
+pub const NAMES_SCHEMA: &str = include_str!("../schema/zj-pane-names.schema.json");
+
+/// JSON export format for pane metadata
+///
+/// `panes` stays a flat id -> name map so existing `jq '.panes'` consumers keep
+/// working; richer per-pane details live in `pane_info`.
+#[derive(Serialize)]
+pub struct PaneNamesExport {
+    pub producer: ProducerInfo,
+    pub panes: BTreeMap<String, String>,
+    /// Logical id -> current pane id, for scripts that must survive session restarts
+    pub logical_ids: BTreeMap<String, String>,
+    /// 1-based tab position -> pane ids in reading order
+    pub tabs: BTreeMap<usize, Vec<String>>,
+    /// 1-based position of the tab the user is looking at
+    pub active_tab: Option<usize>,
+    /// Client id -> 1-based position of the tab that client is looking at
+    pub client_tabs: BTreeMap<u16, usize>,
+    /// Failed panes nobody has acknowledged yet
+    pub alerts: Vec<String>,
+    /// Failed panes whose alert was acknowledged, until they are closed or
+    /// re-run successfully
+    pub acknowledged_alerts: Vec<String>,
+    /// Names shared by several terminal panes, with their ids. Readers
+    /// looking panes up by name should use `logical_ids` or ids for these.
+    pub duplicate_names: BTreeMap<String, Vec<String>>,
+    pub pane_info: BTreeMap<String, PaneInfoExport>,
+    pub stats: Stats,
+    pub timestamp: u64,
+}
+
+impl PaneNamesExport {
+    /// Hash of everything but `stats` and `timestamp`, which change with every
+    /// export. An unchanged digest means the file would tell readers nothing new.
+    pub fn digest(&self) -> u64 {
+        let content = serde_json::to_vec(&(
+            &self.producer,
+            &self.panes,
+            &self.logical_ids,
+            &self.tabs,
+            (&self.active_tab, &self.client_tabs),
+            (&self.alerts, &self.acknowledged_alerts),
+            &self.duplicate_names,
+            &self.pane_info,
+        ))
+        .unwrap_or_default();
+        identity::fnv1a(&content)
+    }
+}
+
+/// Who wrote the export, for debugging mismatches between the plugin and
+/// the tools reading its files
+#[derive(Serialize, Clone, Default)]
+pub struct ProducerInfo {
+    pub plugin_version: &'static str,
+    pub zellij_version: Option<String>,
+    pub hostname: Option<String>,
+    /// When the plugin was loaded, seconds since the epoch
+    pub loaded_at: u64,
+}
+
+impl ProducerInfo {
+    pub fn new(zellij_version: String, loaded_at: u64) -> Self {
+        ProducerInfo {
+            plugin_version: env!("CARGO_PKG_VERSION"),
+            zellij_version: Some(zellij_version).filter(|v| !v.is_empty()),
+            hostname: None,
+            loaded_at,
+        }
+    }
+}
+
+/// Position and size of a floating pane, and when it last changed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FloatingGeometry {
+    pub x: usize,
+    pub y: usize,
+    pub columns: usize,
+    pub rows: usize,
+    /// When the pane was last moved or resized (or first seen floating)
+    pub moved_at: u64,
+}
+
+impl FloatingGeometry {
+    pub fn of(info: &PaneInfo, moved_at: u64) -> Self {
+        FloatingGeometry {
+            x: info.pane_x,
+            y: info.pane_y,
+            columns: info.pane_columns,
+            rows: info.pane_rows,
+            moved_at,
+        }
+    }
+
+    pub fn same_place(&self, other: &FloatingGeometry) -> bool {
+        (self.x, self.y, self.columns, self.rows) == (other.x, other.y, other.columns, other.rows)
+    }
+}
+
+/// Per-pane details exported alongside the names
+#[derive(Serialize)]
+pub struct PaneInfoExport {
+    pub name: String,
+    /// 1-based position of the pane's tab
+    pub tab: usize,
+    /// 1-based place of the pane in its tab's reading order (see `tabs`)
+    pub position: usize,
+    /// Stable across session restarts as long as name, command and cwd stay the same
+    pub logical_id: String,
+    pub command: Option<String>,
+    pub is_plugin: bool,
+    /// Whether the pane can receive focus (false for status bars and similar UI panes)
+    pub selectable: bool,
+    /// Suppressed panes are hidden from the user but keep running, e.g. the pane
+    /// behind a scrollback editor
+    pub suppressed: bool,
+    /// The pane's command has exited and the pane is waiting to be closed or re-run
+    pub exited: bool,
+    pub exit_status: Option<i32>,
+    /// Category assigned by `category_rules`
+    pub category: Option<String>,
+    /// Tags given in the tracker's bulk menu
+    pub tags: Vec<String>,
+    /// Icon for the pane's program, for reports and other renderers
+    pub icon: Option<String>,
+    /// Host the pane is connected to, for `ssh`/`mosh` command lines
+    pub remote_host: Option<String>,
+    /// Container or pod the pane is attached to, for `docker`/`podman`/
+    /// `kubectl` `exec`, `attach` and `run` command lines
+    pub container: Option<ContainerRef>,
+    /// Another terminal pane has the same name
+    pub duplicate_name: bool,
+    /// The latest capture hit `max_capture_bytes` and only holds its tail
+    pub truncated: bool,
+    /// The user is reading this pane's scrollback in scroll or search mode,
+    /// so new output isn't being watched
+    pub scrolled: bool,
+    /// When the pane's captured content last changed, seconds since the
+    /// epoch. Unset until the pane has been captured.
+    pub last_changed_at: Option<u64>,
+    /// Size, line counts and error lines of the latest capture
+    pub content: Option<ContentStats>,
+    /// Repository and branch of the pane's cwd, known for panes opened by
+    /// the tracker
+    pub git: Option<GitInfo>,
+    /// Pane the tracker opened this one for: the tracker itself for
+    /// templates, the source pane for re-runs and mirrors
+    pub spawned_by: Option<String>,
+    /// Set for floating panes
+    pub floating: Option<FloatingGeometry>,
+    /// Set for panes in a stack; collapsed members overlap the expanded one's
+    /// column span
+    pub stack: Option<StackMembership>,
+}
+
+impl PaneNamesExport {
+    pub fn new(state: &State, timestamp: u64) -> Self {
+        let mut stacks = layout::find_stacks(&state.panes);
+        let ids = &state.config.pane_id_format;
+        let order = layout::tab_order(&state.panes);
+        let alerts: Vec<u32> = state.alerting().collect();
+        let duplicates = state.duplicate_names();
+        let positions: BTreeMap<&String, usize> = order
+            .values()
+            .flat_map(|tab| tab.iter().enumerate().map(|(index, id)| (id, index + 1)))
+            .collect();
+        PaneNamesExport {
+            producer: state.producer.clone(),
+            panes: state
+                .panes
+                .iter()
+                .map(|(id, pane)| (ids.apply(id), pane.info.title.clone()))
+                .collect(),
+            logical_ids: state
+                .panes
+                .iter()
+                .map(|(id, pane)| (pane.logical_id.clone(), ids.apply(id)))
+                .collect(),
+            tabs: order
+                .iter()
+                .map(|(tab, panes)| (tab + 1, panes.iter().map(|id| ids.apply(id)).collect()))
+                .collect(),
+            active_tab: state.active_tab.map(|tab| tab + 1),
+            client_tabs: state
+                .client_tabs
+                .iter()
+                .map(|(client, tab)| (*client, tab + 1))
+                .collect(),
+            alerts: alerts
+                .iter()
+                .filter(|id| state.is_unacknowledged(**id))
+                .map(|id| ids.apply(&format!("terminal_{}", id)))
+                .collect(),
+            acknowledged_alerts: alerts
+                .iter()
+                .filter(|id| !state.is_unacknowledged(**id))
+                .map(|id| ids.apply(&format!("terminal_{}", id)))
+                .collect(),
+            duplicate_names: duplicates
+                .iter()
+                .map(|(name, panes)| {
+                    (
+                        name.to_string(),
+                        panes.iter().map(|id| ids.apply(id)).collect(),
+                    )
+                })
+                .collect(),
+            pane_info: state
+                .panes
+                .iter()
+                .map(|(id, pane)| {
+                    let mut info = PaneInfoExport::new(state, pane);
+                    info.stack = stacks.remove(id);
+                    info.tags = state
+                        .tags
+                        .get(id)
+                        .map(|tags| tags.iter().cloned().collect())
+                        .unwrap_or_default();
+                    info.position = positions.get(id).copied().unwrap_or_default();
+                    info.duplicate_name = duplicates
+                        .get(pane.info.title.as_str())
+                        .is_some_and(|panes| panes.contains(&id));
+                    (ids.apply(id), info)
+                })
+                .collect(),
+            stats: state.stats.clone(),
+            timestamp,
+        }
+    }
+}
+
+impl PaneInfoExport {
+    fn new(state: &State, pane: &TrackedPane) -> Self {
+        let terminal_id = (!pane.info.is_plugin).then_some(pane.info.id);
+        PaneInfoExport {
+            name: pane.info.title.clone(),
+            tab: pane.tab + 1,
+            position: 0,
+            logical_id: pane.logical_id.clone(),
+            command: pane.info.terminal_command.clone(),
+            is_plugin: pane.info.is_plugin,
+            selectable: pane.info.is_selectable,
+            suppressed: pane.info.is_suppressed,
+            exited: pane.info.exited,
+            exit_status: pane.info.exit_status,
+            category: pane.category.clone(),
+            tags: Vec::new(),
+            icon: pane.icon.clone(),
+            remote_host: pane.remote_host.clone(),
+            container: pane.container.clone(),
+            duplicate_name: false,
+            truncated: terminal_id.is_some_and(|id| state.truncated.contains(&id)),
+            scrolled: state.is_scrolled(pane),
+            last_changed_at: terminal_id.and_then(|id| state.last_changed.get(&id).copied()),
+            content: terminal_id.and_then(|id| state.content_stats.get(&id).cloned()),
+            git: terminal_id.and_then(|id| state.git_info.get(&id).cloned()),
+            spawned_by: terminal_id
+                .and_then(|id| state.spawned_by.get(&id))
+                .map(|by| state.config.pane_id_format.apply(by)),
+            floating: pane.floating.clone(),
+            stack: None,
+        }
+    }
+}
+
+/// Small liveness file rewritten on a fixed timer
+#[derive(Serialize)]
+pub struct HeartbeatExport {
+    pub timestamp: u64,
+    pub session: Option<String>,
+    pub pane_count: usize,
+    pub paused: bool,
+}
+
+/// What a pane looked like at the previous export, for change detection
+#[derive(Clone, PartialEq, Eq)]
+pub struct PaneSnapshot {
+    pub name: String,
+    pub focused: bool,
+}
+
+impl PaneSnapshot {
+    pub fn of(panes: &BTreeMap<String, TrackedPane>) -> BTreeMap<String, PaneSnapshot> {
+        panes
+            .iter()
+            .map(|(id, pane)| {
+                let snapshot = PaneSnapshot {
+                    name: pane.info.title.clone(),
+                    focused: pane.info.is_focused,
+                };
+                (id.clone(), snapshot)
+            })
+            .collect()
+    }
+}
+
+/// A pane whose name differs from the previous export
+#[derive(Serialize)]
+pub struct Rename {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Panes that changed since the previous export, written to `zj-changes.json`
+#[derive(Serialize, Default)]
+pub struct ChangesExport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<Rename>,
+    /// Panes that became focused in their tab
+    pub refocused: Vec<String>,
+    /// Terminal panes whose captured content changed
+    pub content_changed: Vec<String>,
+    pub timestamp: u64,
+}
+
+impl ChangesExport {
+    /// Every pane id mentioned, once each
+    pub fn changed_ids(&self) -> BTreeSet<&String> {
+        self.added
+            .iter()
+            .chain(&self.removed)
+            .chain(&self.refocused)
+            .chain(&self.content_changed)
+            .chain(self.renamed.iter().map(|rename| &rename.id))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.refocused.is_empty()
+            && self.content_changed.is_empty()
+    }
+
+    pub fn new(
+        previous: &BTreeMap<String, PaneSnapshot>,
+        current: &BTreeMap<String, PaneSnapshot>,
+        content_changed: &BTreeSet<u32>,
+        ids: &PaneIdFormat,
+        timestamp: u64,
+    ) -> Self {
+        let mut changes = ChangesExport {
+            timestamp,
+            ..Default::default()
+        };
+        for (id, pane) in current {
+            match previous.get(id) {
+                None => changes.added.push(id.clone()),
+                Some(before) => {
+                    if before.name != pane.name {
+                        changes.renamed.push(Rename {
+                            id: id.clone(),
+                            from: before.name.clone(),
+                            to: pane.name.clone(),
+                        });
+                    }
+                    if pane.focused && !before.focused {
+                        changes.refocused.push(id.clone());
+                    }
+                }
+            }
+        }
+        changes.removed = previous
+            .keys()
+            .filter(|id| !current.contains_key(*id))
+            .cloned()
+            .collect();
+        changes.content_changed = content_changed
+            .iter()
+            .map(|id| format!("terminal_{}", id))
+            .filter(|id| current.contains_key(id))
+            .collect();
+        for id in changes
+            .added
+            .iter_mut()
+            .chain(&mut changes.removed)
+            .chain(&mut changes.refocused)
+            .chain(&mut changes.content_changed)
+            .chain(changes.renamed.iter_mut().map(|rename| &mut rename.id))
+        {
+            *id = ids.apply(id);
+        }
+        changes
+    }
+}
@@ -0,0 +1,110 @@
+use crate::TrackedPane;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zellij_tile::prelude::PaneInfo;
+
+/// The `PaneInfo` fields the tracker reads, with defaults for the rest, so
+/// recorded manifests stay short enough to read and edit by hand
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixturePane {
+    pub id: u32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_plugin: bool,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin_url: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_focused: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_floating: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub is_suppressed: bool,
+    /// Panes are selectable unless a fixture says otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selectable: Option<bool>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub exited: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_status: Option<i32>,
+    pub x: usize,
+    pub y: usize,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// A pane manifest as recorded: 0-based tab position -> panes
+pub type FixtureManifest = BTreeMap<usize, Vec<FixturePane>>;
+
+impl FixturePane {
+    fn of(info: &PaneInfo) -> Self {
+        FixturePane {
+            id: info.id,
+            is_plugin: info.is_plugin,
+            title: info.title.clone(),
+            command: info.terminal_command.clone(),
+            plugin_url: info.plugin_url.clone(),
+            is_focused: info.is_focused,
+            is_floating: info.is_floating,
+            is_suppressed: info.is_suppressed,
+            selectable: Some(info.is_selectable).filter(|selectable| !selectable),
+            exited: info.exited,
+            exit_status: info.exit_status,
+            x: info.pane_x,
+            y: info.pane_y,
+            columns: info.pane_columns,
+            rows: info.pane_rows,
+        }
+    }
+
+    #[cfg(test)]
+    fn to_info(&self) -> PaneInfo {
+        PaneInfo {
+            id: self.id,
+            is_plugin: self.is_plugin,
+            title: self.title.clone(),
+            terminal_command: self.command.clone(),
+            plugin_url: self.plugin_url.clone(),
+            is_focused: self.is_focused,
+            is_floating: self.is_floating,
+            is_suppressed: self.is_suppressed,
+            is_selectable: self.selectable.unwrap_or(true),
+            exited: self.exited,
+            exit_status: self.exit_status,
+            pane_x: self.x,
+            pane_content_x: self.x + 1,
+            pane_y: self.y,
+            pane_content_y: self.y + 1,
+            pane_columns: self.columns,
+            pane_content_columns: self.columns.saturating_sub(2),
+            pane_rows: self.rows,
+            pane_content_rows: self.rows.saturating_sub(2),
+            ..PaneInfo::default()
+        }
+    }
+}
+
+/// The manifest the tracked panes came from, for recording a session
+pub fn record(panes: &BTreeMap<String, TrackedPane>) -> FixtureManifest {
+    let mut manifest = FixtureManifest::new();
+    for pane in panes.values() {
+        manifest
+            .entry(pane.tab)
+            .or_default()
+            .push(FixturePane::of(&pane.info));
+    }
+    manifest
+}
+
+/// Turn a recorded manifest back into what Zellij sends
+#[cfg(test)]
+pub fn to_manifest(manifest: &FixtureManifest) -> zellij_tile::prelude::PaneManifest {
+    zellij_tile::prelude::PaneManifest {
+        panes: manifest
+            .iter()
+            .map(|(tab, panes)| (*tab, panes.iter().map(FixturePane::to_info).collect()))
+            .collect(),
+    }
+}
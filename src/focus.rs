@@ -0,0 +1,124 @@
+use crate::commands::CommandKind;
+use crate::{terminal_id, unix_now, State};
+
+/// First line of `focus-timeline.csv`
+pub const TIMELINE_HEADER: &str = "timestamp,pane_id,pane_name,duration,tags\n";
+
+/// Focus intervals shorter than this, flicking through panes, aren't written
+const MIN_FOCUS_SECS: u64 = 2;
+
+/// The terminal pane the user has been looking at, since when
+#[derive(Debug, Clone)]
+pub struct FocusSpan {
+    pub pane_id: String,
+    /// Latest name and tags, so a span that ends with its pane closing
+    /// still has them
+    pub name: String,
+    pub tags: String,
+    pub since: u64,
+}
+
+/// Seconds since the epoch as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn iso8601(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+    // Days to civil date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Quote a CSV field when it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl State {
+    /// The focused terminal pane of the active tab, floating panes first
+    fn focused_terminal(&self) -> Option<&String> {
+        let tab = self.active_tab?;
+        let mut focused: Vec<_> = self
+            .panes
+            .iter()
+            .filter(|(id, pane)| {
+                pane.tab == tab && pane.info.is_focused && terminal_id(id).is_some()
+            })
+            .collect();
+        focused.sort_by_key(|(_, pane)| !pane.info.is_floating);
+        focused.first().map(|(id, _)| *id)
+    }
+
+    /// Close the current focus span when another pane got focus, appending
+    /// it to `focus-timeline.csv`
+    pub(crate) fn track_focus(&mut self) {
+        if !self.config.focus_timeline {
+            return;
+        }
+        let now = unix_now();
+        let focused = self.focused_terminal().cloned();
+        if let Some(span) = self.focus.take() {
+            if Some(&span.pane_id) == focused.as_ref() {
+                self.focus = Some(self.focus_span(span.pane_id, span.since));
+                return;
+            }
+            self.write_focus_span(&span, now);
+        }
+        self.focus = focused.map(|pane_id| self.focus_span(pane_id, now));
+    }
+
+    fn focus_span(&self, pane_id: String, since: u64) -> FocusSpan {
+        let tags: Vec<&str> = self
+            .tags
+            .get(&pane_id)
+            .map(|tags| tags.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+        FocusSpan {
+            name: self.panes[&pane_id].info.title.clone(),
+            tags: tags.join(";"),
+            pane_id,
+            since,
+        }
+    }
+
+    fn write_focus_span(&mut self, span: &FocusSpan, now: u64) {
+        let duration = now.saturating_sub(span.since);
+        if duration < MIN_FOCUS_SECS || self.paused {
+            return;
+        }
+        let row = format!(
+            "{},{},{},{},{}\n",
+            iso8601(span.since),
+            csv_field(&self.config.pane_id_format.apply(&span.pane_id)),
+            csv_field(&span.name),
+            duration,
+            csv_field(&span.tags)
+        );
+        let path = self.config.focus_timeline_path();
+        self.append_file(
+            CommandKind::FocusTimeline,
+            &path,
+            Some(TIMELINE_HEADER),
+            &row,
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::host::Shell;
+use serde::Serialize;
+use std::path::Path;
+
+/// Repository and branch a pane's working directory belongs to
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GitInfo {
+    /// Name of the repository's top-level directory
+    pub repo: String,
+    pub branch: String,
+}
+
+impl GitInfo {
+    /// Parse the output of `git_info_cmd`: the top-level path, then the branch
+    pub fn parse(stdout: &str) -> Option<Self> {
+        let mut lines = stdout.lines().map(str::trim);
+        let toplevel = lines.next().filter(|l| !l.is_empty())?;
+        let branch = lines.next().filter(|l| !l.is_empty())?;
+        Some(GitInfo {
+            repo: toplevel.rsplit('/').next().unwrap_or(toplevel).to_string(),
+            branch: branch.to_string(),
+        })
+    }
+
+    /// `repo-branch` with anything unsafe in a file name replaced by `-`
+    pub fn slug(&self) -> String {
+        format!("{}-{}", self.repo, self.branch)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    }
+}
+
+/// Command line printing the repository root and current branch for `cwd`.
+/// Fails outside a repository.
+pub fn git_info_cmd(cwd: &Path) -> Vec<String> {
+    vec![
+        "git".to_string(),
+        "-C".to_string(),
+        cwd.display().to_string(),
+        "rev-parse".to_string(),
+        "--show-toplevel".to_string(),
+        "--abbrev-ref".to_string(),
+        "HEAD".to_string(),
+    ]
+}
+
+/// Command line that commits the tracker's files in `dir` to a git repository
+/// there, creating it on first use. Does nothing when they're unchanged.
+pub fn snapshot_commit_cmd(shell: &Shell, dir: &str, message: &str) -> Vec<String> {
+    // The .gitignore limits the repository to the tracker's own files, so
+    // a shared export_dir doesn't sweep in anything else. It's rewritten
+    // every time so existing repositories pick up new patterns.
+    let script = "cd \"$1\" || exit 1
+if [ ! -d .git ]; then
+  git init -q || exit 1
+fi
+printf '%s\\n' '*' '!zj-*.json' '!zj-*.msgpack' '!zj-*.txt' 'zj-tracker-heartbeat.json' > .gitignore
+git add -A
+git diff --cached --quiet || git -c user.name=zellij-pane-tracker -c user.email=zellij-pane-tracker@localhost commit -qm \"$2\"";
+    shell.script(script, [dir, message])
+}
@@ -0,0 +1,254 @@
+use crate::commands::CommandKind;
+use crate::diff::ManifestDiff;
+use crate::host;
+use crate::{unix_now, State, TrackedPane};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Pane events a user command can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookEvent {
+    Open,
+    Close,
+    Rename,
+    /// A command pane exited with a non-zero status
+    Alert,
+    /// A pane started a command matching a `command_watchers` entry
+    Command,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 5] = [
+        HookEvent::Open,
+        HookEvent::Close,
+        HookEvent::Rename,
+        HookEvent::Alert,
+        HookEvent::Command,
+    ];
+
+    /// Configuration key holding the event's command
+    pub fn config_key(self) -> &'static str {
+        match self {
+            HookEvent::Open => "on_pane_open",
+            HookEvent::Close => "on_pane_close",
+            HookEvent::Rename => "on_pane_rename",
+            HookEvent::Alert => "on_alert",
+            HookEvent::Command => "on_command",
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HookEvent::Open => "open",
+            HookEvent::Close => "close",
+            HookEvent::Rename => "rename",
+            HookEvent::Alert => "alert",
+            HookEvent::Command => "command",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Shell commands to run on pane events, keyed by event
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    commands: BTreeMap<&'static str, String>,
+}
+
+impl Hooks {
+    pub fn set(&mut self, event: HookEvent, command: &str) {
+        if command.trim().is_empty() {
+            self.commands.remove(event.config_key());
+        } else {
+            self.commands
+                .insert(event.config_key(), command.to_string());
+        }
+    }
+
+    pub fn get(&self, event: HookEvent) -> Option<&str> {
+        self.commands.get(event.config_key()).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+}
+
+/// A pane event with the pane's details, passed to hooks as environment
+/// variables and published as JSON
+#[derive(Serialize)]
+pub struct PaneEvent {
+    pub event: HookEvent,
+    pub session: Option<String>,
+    pub pane_id: String,
+    pub name: String,
+    pub command: Option<String>,
+    /// 1-based tab position
+    pub tab: usize,
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_name: Option<String>,
+    pub exit_status: Option<i32>,
+    /// Name of the `command_watchers` entry that matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watcher: Option<String>,
+    pub timestamp: u64,
+}
+
+impl PaneEvent {
+    /// Hook environment: `ZJ_EVENT`, `ZJ_SESSION` and the pane's details as
+    /// `ZJ_PANE_*`
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("ZJ_EVENT", self.event.to_string()),
+            ("ZJ_SESSION", self.session.clone().unwrap_or_default()),
+            ("ZJ_PANE_ID", self.pane_id.clone()),
+            ("ZJ_PANE_NAME", self.name.clone()),
+            ("ZJ_PANE_COMMAND", self.command.clone().unwrap_or_default()),
+            ("ZJ_PANE_TAB", self.tab.to_string()),
+            (
+                "ZJ_PANE_CATEGORY",
+                self.category.clone().unwrap_or_default(),
+            ),
+        ];
+        if let Some(ref old_name) = self.old_name {
+            env.push(("ZJ_PANE_OLD_NAME", old_name.clone()));
+        }
+        if let Some(code) = self.exit_status {
+            env.push(("ZJ_PANE_EXIT_STATUS", code.to_string()));
+        }
+        if let Some(ref watcher) = self.watcher {
+            env.push(("ZJ_WATCHER", watcher.clone()));
+        }
+        env
+    }
+}
+
+impl State {
+    /// Run the hooks and publish the events for panes the manifest update
+    /// opened, closed, renamed, saw exit with an error or saw start a
+    /// watched command. Watched commands are also appended to the command
+    /// log, and both kinds of alert to the alerts log, even in quiet hours.
+    pub(crate) fn run_pane_hooks(&mut self, diff: &ManifestDiff) {
+        self.log_watched_commands(diff);
+        self.log_alerts(diff);
+        if self.config.hooks.is_empty() && self.config.mqtt_topic.is_none() {
+            return;
+        }
+        let mut events = self.command_events(diff);
+        for pane_id in &diff.added {
+            if let Some(pane) = self.panes.get(pane_id) {
+                events.push(self.pane_event(HookEvent::Open, pane_id, pane, None));
+            }
+        }
+        for (pane_id, old_name) in &diff.renamed {
+            if let Some(pane) = self.panes.get(pane_id) {
+                let old_name = Some(old_name.clone());
+                events.push(self.pane_event(HookEvent::Rename, pane_id, pane, old_name));
+            }
+        }
+        for pane_id in &diff.failed {
+            if let Some(pane) = self.panes.get(pane_id) {
+                events.push(self.pane_event(HookEvent::Alert, pane_id, pane, None));
+            }
+        }
+        for (pane_id, pane) in &diff.removed {
+            events.push(self.pane_event(HookEvent::Close, pane_id, pane, None));
+        }
+
+        if !events.is_empty() && self.in_quiet_hours(unix_now()) {
+            self.log.info(format!(
+                "quiet hours: held back {} pane events",
+                events.len()
+            ));
+            return;
+        }
+        for event in &events {
+            if let Some(hook) = self.config.hooks.get(event.event) {
+                let argv = self.config.shell.cmd_with_env(hook, &event.env());
+                self.log.debug(format!("hook {}: {}", event.event, hook));
+                self.commands.run(CommandKind::Hook(event.event), &argv);
+            }
+            self.publish_event(event);
+        }
+    }
+
+    fn command_events(&self, diff: &ManifestDiff) -> Vec<PaneEvent> {
+        diff.commands
+            .iter()
+            .filter_map(|(pane_id, watcher)| {
+                let pane = self.panes.get(pane_id)?;
+                let mut event = self.pane_event(HookEvent::Command, pane_id, pane, None);
+                event.watcher = Some(watcher.clone());
+                Some(event)
+            })
+            .collect()
+    }
+
+    /// Append one JSON line per watched command to `zj-commands.jsonl`
+    fn log_watched_commands(&mut self, diff: &ManifestDiff) {
+        let lines: String = self
+            .command_events(diff)
+            .iter()
+            .filter_map(|event| serde_json::to_string(event).ok())
+            .map(|line| line + "\n")
+            .collect();
+        if lines.is_empty() {
+            return;
+        }
+        let path = self.config.commands_log_path();
+        self.append_file(CommandKind::CommandLog, &path, None, &lines);
+    }
+
+    fn pane_event(
+        &self,
+        event: HookEvent,
+        pane_id: &str,
+        pane: &TrackedPane,
+        old_name: Option<String>,
+    ) -> PaneEvent {
+        PaneEvent {
+            event,
+            session: self.session_name.clone(),
+            pane_id: self.config.pane_id_format.apply(pane_id),
+            name: pane.info.title.clone(),
+            command: pane.info.terminal_command.clone(),
+            tab: pane.tab + 1,
+            category: pane.category.clone(),
+            old_name,
+            exit_status: pane.info.exit_status,
+            watcher: None,
+            timestamp: unix_now(),
+        }
+    }
+
+    /// Publish an event as JSON to `<mqtt_topic>/<event>`
+    fn publish_event(&mut self, event: &PaneEvent) {
+        let Some(ref topic) = self.config.mqtt_topic else {
+            return;
+        };
+        let payload = match serde_json::to_string(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                self.log
+                    .error(format!("mqtt: failed to serialize event: {}", e));
+                return;
+            }
+        };
+        let cmd = format!(
+            "{} -t {} -s",
+            self.config.mqtt_pub_cmd,
+            host::shell_quote(&format!("{}/{}", topic, event.event))
+        );
+        let argv = self.config.shell.pipe_to_cmd(&cmd, &payload);
+        self.commands.run(CommandKind::Publish(event.event), &argv);
+    }
+}
@@ -0,0 +1,160 @@
+/// Largest single argument passed to the shell. Linux rejects arguments over
+/// 128 KiB (MAX_ARG_STRLEN), so bigger contents are split across several.
+const MAX_ARG_BYTES: usize = 64 * 1024;
+
+/// Shell that host commands needing redirection or pipes are run with,
+/// `sh` unless configured otherwise (e.g. `busybox sh` or `/bin/bash`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shell {
+    argv: Vec<String>,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell {
+            argv: vec!["sh".to_string()],
+        }
+    }
+}
+
+impl Shell {
+    /// Parse a shell given as a program followed by any arguments it needs
+    /// before `-c`
+    pub fn parse(value: &str) -> Option<Self> {
+        let argv: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+        if argv.is_empty() {
+            None
+        } else {
+            Some(Shell { argv })
+        }
+    }
+
+    /// Command line running `script` with `args` as its positional parameters
+    pub fn script<I>(&self, script: &str, args: I) -> Vec<String>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let mut argv = self.argv.clone();
+        argv.extend(["-c".to_string(), script.to_string(), "sh".to_string()]);
+        argv.extend(args.into_iter().map(Into::into));
+        argv
+    }
+
+    /// Command line that writes `contents` to `path` on the host, replacing the file
+    ///
+    /// The contents are passed as positional arguments rather than spliced into
+    /// the script, so no shell escaping is needed.
+    pub fn write_file_cmd(&self, path: &str, contents: &str) -> Vec<String> {
+        self.write(
+            "f=\"$1\"; shift; printf '%s' \"$@\" > \"$f\"",
+            path,
+            contents,
+        )
+    }
+
+    /// Command line that writes `contents` to `path` after passing it through the
+    /// shell command `cmd` (e.g. `age -r <recipient>`), replacing the file. The
+    /// file is created readable by the owner only.
+    pub fn write_file_through_cmd(&self, cmd: &str, path: &str, contents: &str) -> Vec<String> {
+        self.write(
+            &format!(
+                "f=\"$1\"; shift; umask 077; printf '%s' \"$@\" | {} > \"$f\"",
+                cmd
+            ),
+            path,
+            contents,
+        )
+    }
+
+    /// Command line that decodes the base64 `encoded` and writes the bytes to
+    /// `path`, through the shell command `cmd` if given. Arguments can't carry
+    /// NUL bytes, so binary contents travel encoded.
+    pub fn write_base64_file_cmd(
+        &self,
+        cmd: Option<&str>,
+        path: &str,
+        encoded: &str,
+    ) -> Vec<String> {
+        let script = match cmd {
+            Some(cmd) => format!(
+                "f=\"$1\"; shift; umask 077; printf '%s' \"$@\" | base64 -d | {} > \"$f\"",
+                cmd
+            ),
+            None => "f=\"$1\"; shift; printf '%s' \"$@\" | base64 -d > \"$f\"".to_string(),
+        };
+        self.write(&script, path, encoded)
+    }
+
+    /// Command line that appends `contents` to `path` on the host
+    pub fn append_file_cmd(&self, path: &str, contents: &str) -> Vec<String> {
+        self.write(
+            "f=\"$1\"; shift; printf '%s' \"$@\" >> \"$f\"",
+            path,
+            contents,
+        )
+    }
+
+    /// Like `append_file_cmd`, but a missing or empty file starts with `header`
+    pub fn append_with_header_cmd(&self, path: &str, header: &str, contents: &str) -> Vec<String> {
+        self.script(
+            "f=\"$1\"; h=\"$2\"; shift 2; [ -s \"$f\" ] || printf '%s' \"$h\" > \"$f\"; printf '%s' \"$@\" >> \"$f\"",
+            [path, header, contents],
+        )
+    }
+
+    /// Command line that feeds `contents` to the stdin of the shell command `cmd`
+    pub fn pipe_to_cmd(&self, cmd: &str, contents: &str) -> Vec<String> {
+        self.write(
+            &format!("shift; printf '%s' \"$@\" | {}", cmd),
+            "",
+            contents,
+        )
+    }
+
+    /// Command line running the shell command `cmd` with `env` added to its environment
+    pub fn cmd_with_env(&self, cmd: &str, env: &[(&str, String)]) -> Vec<String> {
+        let mut argv = vec!["env".to_string()];
+        argv.extend(env.iter().map(|(key, value)| format!("{}={}", key, value)));
+        argv.extend(self.argv.iter().cloned());
+        argv.extend(["-c".to_string(), cmd.to_string()]);
+        argv
+    }
+
+    fn write(&self, script: &str, path: &str, contents: &str) -> Vec<String> {
+        let mut args = vec![path.to_string()];
+        args.extend(chunks(contents).map(str::to_string));
+        if args.len() == 1 {
+            args.push(String::new());
+        }
+        self.script(script, args)
+    }
+}
+
+/// Quote `s` as a single shell word
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Command line that prints the file at `path`. Runs `cat` directly, so a
+/// missing file shows up as a failed command.
+pub fn read_file_cmd(path: &str) -> Vec<String> {
+    vec!["cat".to_string(), path.to_string()]
+}
+
+/// Split `s` into pieces of at most `MAX_ARG_BYTES`, on char boundaries
+fn chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = rest.len().min(MAX_ARG_BYTES);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
@@ -0,0 +1,75 @@
+use crate::category::program_name;
+use std::collections::BTreeMap;
+
+/// Built-in program -> icon map. Nerd Font glyphs where one exists, emoji otherwise.
+const DEFAULT_ICONS: &[(&str, &str)] = &[
+    ("vim", "\u{e62b}"),
+    ("nvim", "\u{e62b}"),
+    ("vi", "\u{e62b}"),
+    ("git", "\u{e702}"),
+    ("lazygit", "\u{e702}"),
+    ("docker", "🐳"),
+    ("podman", "🐳"),
+    ("kubectl", "☸"),
+    ("cargo", "\u{e7a8}"),
+    ("rustc", "\u{e7a8}"),
+    ("python", "\u{e73c}"),
+    ("python3", "\u{e73c}"),
+    ("node", "\u{e718}"),
+    ("npm", "\u{e718}"),
+    ("bun", "\u{e718}"),
+    ("ssh", "🔑"),
+    ("htop", "📈"),
+    ("btop", "📈"),
+    ("make", "🔨"),
+    ("tail", "📜"),
+    ("less", "📜"),
+    ("bash", "\u{f489}"),
+    ("zsh", "\u{f489}"),
+    ("fish", "\u{f489}"),
+];
+
+/// Maps program names to icons, starting from the built-ins plus user overrides
+#[derive(Debug, Clone)]
+pub struct IconMap {
+    icons: BTreeMap<String, String>,
+}
+
+impl Default for IconMap {
+    fn default() -> Self {
+        IconMap {
+            icons: DEFAULT_ICONS
+                .iter()
+                .map(|(program, icon)| (program.to_string(), icon.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl IconMap {
+    /// Apply `program=icon` overrides, `;`-separated. An empty icon removes
+    /// the built-in entry. Returns errors for malformed entries.
+    pub fn apply_overrides(&mut self, specs: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        for spec in specs.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            match spec.split_once('=') {
+                Some((program, icon)) if icon.trim().is_empty() => {
+                    self.icons.remove(program.trim());
+                }
+                Some((program, icon)) => {
+                    self.icons
+                        .insert(program.trim().to_string(), icon.trim().to_string());
+                }
+                None => errors.push(format!("'{}' should look like program=icon", spec)),
+            }
+        }
+        errors
+    }
+
+    /// Icon for a pane, looked up by its command's program or, for panes
+    /// without a command, the first word of the title
+    pub fn lookup(&self, title: &str, command: Option<&str>) -> Option<&str> {
+        let program = program_name(command.unwrap_or(title));
+        self.icons.get(program).map(|s| s.as_str())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{terminal_id, State};
+use std::collections::BTreeMap;
+
+/// FNV-1a, chosen because its output never changes between builds, unlike
+/// std's `DefaultHasher`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Logical id of a pane: a short hash of what the pane *is* (name, command
+/// and cwd when known) rather than the numeric id Zellij happened to assign,
+/// so it comes out the same when the session is recreated
+pub fn fingerprint(name: &str, command: Option<&str>, cwd: Option<&str>) -> String {
+    let key = format!(
+        "{}\0{}\0{}",
+        name,
+        command.unwrap_or_default(),
+        cwd.unwrap_or_default()
+    );
+    format!("p{:012x}", fnv1a(key.as_bytes()) & 0xffff_ffff_ffff)
+}
+
+impl State {
+    /// Assign every pane its logical id. Panes that fingerprint the same are
+    /// told apart by a `-2`, `-3`... suffix in pane id order.
+    pub(crate) fn assign_logical_ids(&mut self) {
+        let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+        let mut ids: Vec<(&String, u32)> = self
+            .panes
+            .iter()
+            .map(|(pane_id, pane)| (pane_id, pane.info.id))
+            .collect();
+        // Numeric order, so terminal_10 comes after terminal_9
+        ids.sort_by_key(|(pane_id, id)| (!pane_id.starts_with("terminal_"), *id));
+        let assigned: Vec<(String, String)> = ids
+            .into_iter()
+            .map(|(pane_id, _)| {
+                let pane = &self.panes[pane_id];
+                let cwd = terminal_id(pane_id)
+                    .and_then(|id| self.spawned_cwd.get(&id))
+                    .map(|cwd| cwd.display().to_string());
+                let base = fingerprint(
+                    &pane.info.title,
+                    pane.info.terminal_command.as_deref(),
+                    cwd.as_deref(),
+                );
+                let count = seen.entry(base.clone()).or_insert(0);
+                *count += 1;
+                let logical_id = if *count == 1 {
+                    base
+                } else {
+                    format!("{}-{}", base, count)
+                };
+                (pane_id.clone(), logical_id)
+            })
+            .collect();
+        for (pane_id, logical_id) in assigned {
+            if let Some(pane) = self.panes.get_mut(&pane_id) {
+                pane.logical_id = logical_id;
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::TrackedPane;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+type PaneRef<'a> = (&'a String, &'a TrackedPane);
+
+/// A pane's place in a stack of panes
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct StackMembership {
+    /// Shared by all panes of the stack, e.g. `tab1-stack1`
+    pub id: String,
+    /// The one pane of the stack shown at full height
+    pub expanded: bool,
+}
+
+/// Pane ids of each tab in reading order: tiled panes top to bottom, then
+/// left to right, followed by floating panes the same way. Ties fall back to
+/// the pane id so the order doesn't change between manifests.
+pub fn tab_order(panes: &BTreeMap<String, TrackedPane>) -> BTreeMap<usize, Vec<String>> {
+    let mut tabs: BTreeMap<usize, Vec<PaneRef>> = BTreeMap::new();
+    for (pane_id, pane) in panes {
+        tabs.entry(pane.tab).or_default().push((pane_id, pane));
+    }
+    tabs.into_iter()
+        .map(|(tab, mut entries)| {
+            entries.sort_by_key(|(pane_id, pane)| {
+                (
+                    pane.info.is_floating,
+                    pane.info.pane_y,
+                    pane.info.pane_x,
+                    *pane_id,
+                )
+            });
+            let ids = entries.into_iter().map(|(id, _)| id.clone()).collect();
+            (tab, ids)
+        })
+        .collect()
+}
+
+/// Work out which tiled panes are stacked
+///
+/// Zellij doesn't report stacks to plugins, so they're inferred from
+/// geometry: panes of a stack share their column span and sit directly on
+/// top of each other, and all but the expanded one are a single row tall.
+pub fn find_stacks(panes: &BTreeMap<String, TrackedPane>) -> BTreeMap<String, StackMembership> {
+    let mut columns: BTreeMap<(usize, usize, usize), Vec<PaneRef>> = BTreeMap::new();
+    for (pane_id, pane) in panes {
+        if pane.info.is_floating || pane.info.is_suppressed || !pane.info.is_selectable {
+            continue;
+        }
+        columns
+            .entry((pane.tab, pane.info.pane_x, pane.info.pane_columns))
+            .or_default()
+            .push((pane_id, pane));
+    }
+
+    let mut stacks = BTreeMap::new();
+    let mut count_per_tab: BTreeMap<usize, usize> = BTreeMap::new();
+    for ((tab, _, _), mut column) in columns {
+        column.sort_by_key(|(_, pane)| pane.info.pane_y);
+        let mut runs: Vec<Vec<PaneRef>> = Vec::new();
+        for entry in column {
+            let contiguous = runs
+                .last()
+                .and_then(|run| run.last())
+                .is_some_and(|(_, last)| {
+                    last.info.pane_y + last.info.pane_rows == entry.1.info.pane_y
+                });
+            match runs.last_mut() {
+                Some(run) if contiguous => run.push(entry),
+                _ => runs.push(vec![entry]),
+            }
+        }
+        for run in runs {
+            let collapsed = run.iter().filter(|(_, p)| p.info.pane_rows == 1).count();
+            if run.len() < 2 || collapsed < run.len() - 1 {
+                continue;
+            }
+            let number = count_per_tab.entry(tab).or_insert(0);
+            *number += 1;
+            let id = format!("tab{}-stack{}", tab + 1, number);
+            for (pane_id, pane) in run {
+                let membership = StackMembership {
+                    id: id.clone(),
+                    expanded: pane.info.pane_rows > 1,
+                };
+                stacks.insert(pane_id.clone(), membership);
+            }
+        }
+    }
+    stacks
+}
+
+/// Widest diagram drawn for a tab, in characters
+const DIAGRAM_WIDTH: usize = 78;
+
+/// Box diagram of every tab's tiled panes, scaled down from the panes'
+/// geometry, with floating panes listed underneath. Meant for pasting into a
+/// chat to show how a session is arranged.
+pub fn render_diagram(panes: &BTreeMap<String, TrackedPane>) -> String {
+    let mut tabs: BTreeMap<usize, Vec<PaneRef>> = BTreeMap::new();
+    for entry in panes {
+        if entry.1.info.is_selectable && !entry.1.info.is_suppressed {
+            tabs.entry(entry.1.tab).or_default().push(entry);
+        }
+    }
+
+    let mut out = String::new();
+    for (tab, tab_panes) in tabs {
+        out.push_str(&format!("Tab {}\n", tab + 1));
+        let (floating, tiled): (Vec<PaneRef>, Vec<PaneRef>) =
+            tab_panes.into_iter().partition(|(_, p)| p.info.is_floating);
+        out.push_str(&draw_tiled(&tiled));
+        for (pane_id, pane) in floating {
+            out.push_str(&format!(
+                "  floating: {} ({}) at {},{} {}x{}\n",
+                pane.info.title,
+                pane_id,
+                pane.info.pane_x,
+                pane.info.pane_y,
+                pane.info.pane_columns,
+                pane.info.pane_rows
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn draw_tiled(panes: &[PaneRef]) -> String {
+    let Some(min_x) = panes.iter().map(|(_, p)| p.info.pane_x).min() else {
+        return String::new();
+    };
+    let min_y = panes.iter().map(|(_, p)| p.info.pane_y).min().unwrap_or(0);
+    let max_x = panes
+        .iter()
+        .map(|(_, p)| p.info.pane_x + p.info.pane_columns)
+        .max()
+        .unwrap_or(0);
+    let max_y = panes
+        .iter()
+        .map(|(_, p)| p.info.pane_y + p.info.pane_rows)
+        .max()
+        .unwrap_or(0);
+    let scale = (DIAGRAM_WIDTH as f64 / (max_x - min_x).max(1) as f64).min(1.0);
+    let to_col = |x: usize| ((x - min_x) as f64 * scale).round() as usize;
+    let to_row = |y: usize| ((y - min_y) as f64 * scale).round() as usize;
+
+    let width = to_col(max_x) + 1;
+    let height = to_row(max_y) + 1;
+    let mut grid = vec![vec![' '; width]; height];
+    for (pane_id, pane) in panes {
+        let (left, right) = (
+            to_col(pane.info.pane_x),
+            to_col(pane.info.pane_x + pane.info.pane_columns),
+        );
+        let (top, bottom) = (
+            to_row(pane.info.pane_y),
+            to_row(pane.info.pane_y + pane.info.pane_rows),
+        );
+        for col in left..=right {
+            plot(&mut grid, top, col, '-');
+            plot(&mut grid, bottom, col, '-');
+        }
+        for row in top..=bottom {
+            plot(&mut grid, row, left, '|');
+            plot(&mut grid, row, right, '|');
+        }
+        for (row, col) in [(top, left), (top, right), (bottom, left), (bottom, right)] {
+            grid[row][col] = '+';
+        }
+        let inner = right.saturating_sub(left + 1);
+        let labels = [pane.info.title.as_str(), pane_id.as_str()];
+        for (line, label) in labels.iter().enumerate() {
+            let row = top + 1 + line;
+            if row >= bottom {
+                break;
+            }
+            for (offset, c) in label.chars().take(inner).enumerate() {
+                grid[row][left + 1 + offset] = c;
+            }
+        }
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().collect::<String>().trim_end().to_string() + "\n")
+        .collect()
+}
+
+/// Draw a border character, turning crossings of `-` and `|` into `+`
+fn plot(grid: &mut [Vec<char>], row: usize, col: usize, c: char) {
+    let cell = &mut grid[row][col];
+    *cell = match *cell {
+        ' ' => c,
+        existing if existing == c => c,
+        '-' | '|' | '+' => '+',
+        _ => c,
+    };
+}
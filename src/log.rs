@@ -0,0 +1,101 @@
+/// Severity of a log line, ordered from most to least verbose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// Buffers log lines so they can be appended to the log file in one command
+/// per update, instead of forking a process for every line
+pub struct Logger {
+    level: LogLevel,
+    path: String,
+    pending: Vec<String>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger::new(LogLevel::Warn, crate::config::DEFAULT_LOG_FILE)
+    }
+}
+
+impl Logger {
+    pub fn new(level: LogLevel, path: &str) -> Self {
+        Logger {
+            level,
+            path: path.to_string(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Change the level and file, keeping lines not yet written
+    pub fn reconfigure(&mut self, level: LogLevel, path: &str) {
+        self.level = level;
+        self.path = path.to_string();
+    }
+
+    pub fn debug(&mut self, message: impl AsRef<str>) {
+        self.log(LogLevel::Debug, message.as_ref());
+    }
+
+    pub fn info(&mut self, message: impl AsRef<str>) {
+        self.log(LogLevel::Info, message.as_ref());
+    }
+
+    pub fn warn(&mut self, message: impl AsRef<str>) {
+        self.log(LogLevel::Warn, message.as_ref());
+    }
+
+    pub fn error(&mut self, message: impl AsRef<str>) {
+        self.log(LogLevel::Error, message.as_ref());
+    }
+
+    pub fn log(&mut self, level: LogLevel, message: &str) {
+        if level < self.level {
+            return;
+        }
+        self.pending.push(format!(
+            "[{}] {:<5} {}\n",
+            crate::unix_now(),
+            level.as_str(),
+            message
+        ));
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Drain the buffered lines, if any
+    pub fn take_pending(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let lines = self.pending.concat();
+        self.pending.clear();
+        Some(lines)
+    }
+}
@@ -1,105 +1,1301 @@
-use serde::Serialize;
-use std::collections::BTreeMap;
+mod actions;
+mod alerts;
+mod apply;
+mod bench;
+mod bulk;
+mod capture;
+mod category;
+mod commands;
+mod config;
+mod config_file;
+mod diff;
+mod dot;
+mod dump;
+mod envfile;
+mod export;
+mod fixture;
+mod focus;
+mod git;
+mod hooks;
+mod host;
+mod icons;
+mod identity;
+mod layout;
+mod log;
+mod naming;
+mod persist;
+mod pipe;
+mod profiles;
+mod quiet;
+mod redact;
+mod remote;
+mod sets;
+mod setup;
+mod snapshots;
+mod spawn;
+mod stats;
+mod template;
+#[cfg(test)]
+mod tests;
+mod ui;
+mod watch;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bench::{Cycle, Phase};
+use capture::{CaptureCandidate, CaptureFormat, CaptureScheduler};
+use commands::{CommandKind, CommandOutcome, Commands};
+use config::Config;
+use diff::ManifestDiff;
+use dump::PendingDump;
+use export::{
+    ChangesExport, ExportFormat, FloatingGeometry, HeartbeatExport, PaneNamesExport, PaneSnapshot,
+    ProducerInfo,
+};
+use focus::FocusSpan;
+use git::GitInfo;
+use log::Logger;
+use remote::ContainerRef;
+use sets::SetPane;
+use stats::{ContentStats, Stats};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use ui::UiMode;
 use zellij_tile::prelude::*;
 
+/// Most of an appended file kept in memory under `encrypt_cmd`, see
+/// `append_file`
+const MAX_ENCRYPTED_APPEND_BYTES: usize = 1024 * 1024;
+
 /// Plugin state - tracks pane names and commands across all tabs
 #[derive(Default)]
 struct State {
-    pane_names: BTreeMap<String, String>,
-    pane_commands: BTreeMap<String, String>,
+    config: Config,
+    /// Settings from the plugin block, which override the config file
+    layout_config: BTreeMap<String, String>,
+    /// Whether the config file set anything, so emptying it reverts on reload
+    config_from_file: bool,
+    /// Contents of the redaction rules file, re-applied when the config is rebuilt
+    redact_rules_text: String,
+    log: Logger,
+    commands: Commands,
+    captures: CaptureScheduler,
+    /// Panes from the latest manifest, keyed by `terminal_N` / `plugin_N`
+    panes: BTreeMap<String, TrackedPane>,
+    /// Whether a manifest has arrived since load
+    manifest_seen: bool,
+    /// Latest captured content per terminal pane
+    pane_content: BTreeMap<u32, String>,
+    /// When each terminal pane's captured content last changed
+    last_changed: BTreeMap<u32, u64>,
+    /// Line counts and such of each terminal pane's latest capture
+    content_stats: BTreeMap<u32, ContentStats>,
+    /// Panes as of the previous export, to compute `zj-changes.json`
+    exported_panes: BTreeMap<String, PaneSnapshot>,
+    /// Digest of the contents last written to each export file, so identical
+    /// rewrites can be skipped
+    export_digests: BTreeMap<String, u64>,
+    /// Contents of `zj-changed`, written once the cycle's exports are
+    pending_marker: Option<String>,
+    /// The last file written by the export cycle; once it's on disk the
+    /// snapshot commit, marker and upload run
+    export_awaited: Option<CommandKind>,
+    /// Terminal ids whose content changed since the previous export
+    content_changed: BTreeSet<u32>,
+    /// What this session appended to each file, kept to rewrite the whole
+    /// file through `encrypt_cmd`
+    appended: BTreeMap<String, String>,
+    /// Captures changed exported fields since the previous export; the
+    /// next tick exports them, once for however many captures finished
+    export_due: bool,
+    /// Working directories of panes opened by the tracker
+    spawned_cwd: BTreeMap<u32, std::path::PathBuf>,
+    /// Pane that caused each tracker-opened pane to be spawned
+    spawned_by: BTreeMap<u32, String>,
+    /// Terminal ids the auto-namer has already renamed
+    auto_named: BTreeSet<u32>,
+    /// Failed terminal panes the user has acknowledged
+    acknowledged: BTreeSet<u32>,
+    /// `dump` pipe requests waiting for their capture, by request number
+    pending_dumps: BTreeMap<u64, PendingDump>,
+    next_dump: u64,
+    /// Pane ids marked with Space for a bulk action
+    marked: BTreeSet<String>,
+    /// Tags given to panes from the bulk menu, by pane id
+    tags: BTreeMap<String, BTreeSet<String>>,
+    /// Saved pane sets by name, re-created with `O`
+    pane_sets: BTreeMap<String, Vec<SetPane>>,
+    /// Repository and branch of panes with a known cwd inside a git repo
+    git_info: BTreeMap<u32, GitInfo>,
+    /// Terminal ids whose latest capture was cut to `max_capture_bytes`
+    truncated: BTreeSet<u32>,
+    /// Pane id of the highlighted row in the pane list
+    selected: Option<String>,
+    mode: UiMode,
+    /// Terminal ids matching the last search, best match first
+    search_hits: Vec<u32>,
+    search_index: usize,
+    /// Tab names by 0-based position, for addressing tabs by name
+    tab_names: BTreeMap<usize, String>,
+    /// 0-based position of the tab the tracker's client is looking at
+    active_tab: Option<usize>,
+    /// 0-based position of the tab every connected client is looking at
+    client_tabs: BTreeMap<ClientId, usize>,
+    /// Commits of the snapshot diff being computed
+    pending_diff: Option<(String, Option<String>)>,
+    /// One-line feedback for the last UI action
+    status: Option<String>,
+    /// When `key_prefix` was pressed, while waiting for the action key
+    prefix_pressed: Option<Instant>,
+    /// OSC 52 clipboard sequence to emit on the next render
+    pending_osc52: Option<String>,
+    /// Whether the floating preview follows the selection
+    preview_enabled: bool,
+    /// Terminal id of the open preview pane
+    preview_pane: Option<u32>,
+    /// Hide suppressed and unselectable panes from the list
+    hide_unselectable: bool,
+    /// Answer to the permission request, unset until the user gives one
+    permissions: Option<PermissionStatus>,
+    /// Whether the state file has been read (or found missing)
+    state_read: bool,
+    /// The plugin's cwd as the host sees it, which the sandbox calls `/host`
+    host_cwd: Option<PathBuf>,
+    /// Whether the first-run setup screen was dismissed
+    onboarded: bool,
+    stats: Stats,
+    /// Versions and host, included in every export
+    producer: ProducerInfo,
+    /// When the pending names JSON write was requested, and its size
+    export_started: Option<(Instant, usize)>,
+    session_name: Option<String>,
+    /// Terminal pane focused since when, for `focus_timeline`
+    focus: Option<FocusSpan>,
+    /// Input mode from the latest `ModeUpdate`
+    input_mode: InputMode,
+    /// Update cycle being timed, while `benchmark` is on
+    cycle: Option<Cycle>,
+    last_heartbeat: u64,
+    /// While paused no captures or exports are written
+    paused: bool,
+}
+
+/// A pane as last seen in the manifest
+pub(crate) struct TrackedPane {
+    /// Position of the pane's tab, 0-indexed
+    pub tab: usize,
+    pub info: PaneInfo,
+    /// Name of the first matching `category_rules` entry
+    pub category: Option<String>,
+    /// Icon for the pane's program, from the built-in map and `icons` overrides
+    pub icon: Option<String>,
+    /// Host the pane is connected to over ssh or mosh
+    pub remote_host: Option<String>,
+    /// Container or pod the pane is attached to
+    pub container: Option<ContainerRef>,
+    /// Stable id derived from the pane's name, command and cwd
+    pub logical_id: String,
+    /// Geometry of a floating pane, carried over between manifests so
+    /// `moved_at` only changes when the pane does
+    pub floating: Option<FloatingGeometry>,
 }
 
-/// JSON export format for pane metadata
-#[derive(Serialize)]
-struct PaneNamesExport {
-    panes: BTreeMap<String, String>,
-    timestamp: u64,
+impl TrackedPane {
+    /// Whether the user can focus and interact with the pane
+    pub fn is_interactive(&self) -> bool {
+        self.info.is_selectable && !self.info.is_suppressed
+    }
+
+    /// Whether the pane's command exited with a non-zero status
+    pub fn failed(&self) -> bool {
+        self.info.exited && self.info.exit_status.is_some_and(|code| code != 0)
+    }
+
+    /// Short running/exited label for the list
+    pub fn run_state(&self) -> String {
+        match (self.info.exited, self.info.exit_status) {
+            (true, Some(code)) => format!("exited {}", code),
+            (true, None) => "exited".to_string(),
+            (false, _) => "running".to_string(),
+        }
+    }
 }
 
+/// Context key marking command panes opened by the tracker
+pub(crate) const PANE_ROLE: &str = "pane_tracker_role";
+
 register_plugin!(State);
 
 impl ZellijPlugin for State {
-    fn load(&mut self, _configuration: BTreeMap<String, String>) {
-        subscribe(&[EventType::PaneUpdate]);
-        
-        request_permission(&[
-            PermissionType::ReadApplicationState,
-            PermissionType::RunCommands,
+    fn load(&mut self, configuration: BTreeMap<String, String>) {
+        let (config, warnings) = Config::from_map(&configuration);
+        self.layout_config = configuration;
+        self.log = Logger::new(config.log_level, &config.log_file);
+        self.commands = Commands::new(config.max_concurrent_commands);
+        self.commands.dry_run = config.dry_run;
+        self.hide_unselectable = config.hide_unselectable;
+        self.config = config;
+        self.host_cwd = Some(get_plugin_ids().initial_cwd);
+        for warning in warnings {
+            self.log.warn(format!("config: {}", warning));
+        }
+        self.producer = ProducerInfo::new(get_zellij_version(), unix_now());
+        self.log.info(format!(
+            "plugin {} loaded (zellij {})",
+            self.producer.plugin_version,
+            self.producer.zellij_version.as_deref().unwrap_or("unknown")
+        ));
+
+        subscribe(&[
+            EventType::PaneUpdate,
+            EventType::RunCommandResult,
+            EventType::Timer,
+            EventType::ModeUpdate,
+            EventType::Key,
+            EventType::CommandPaneOpened,
+            EventType::TabUpdate,
+            EventType::PermissionRequestResult,
         ]);
+
+        request_permission(&self.requested_permissions());
+        self.read_export_dir_files();
+        if !self.config.direct_io() {
+            self.run_latest(
+                CommandKind::Hostname,
+                &["uname".to_string(), "-n".to_string()],
+            );
+        }
+        self.read_config_file();
+        self.schedule_tick();
+        self.flush_log();
     }
 
     fn update(&mut self, event: Event) -> bool {
-        match event {
+        self.begin_cycle();
+        let cycle = bench::event_name(&event);
+        let should_render = match event {
             Event::PaneUpdate(pane_manifest) => {
+                let diff = self.start_phase();
                 self.update_pane_info(&pane_manifest);
-                self.export_to_file();
+                self.end_phase(Phase::Diff, diff);
+                if !self.paused {
+                    self.export_to_file();
+                }
+                true
+            }
+            Event::Timer(_) => {
+                if !self.paused {
+                    if self.export_due {
+                        self.export_to_file();
+                    }
+                    self.capture_due_panes();
+                }
+                self.write_heartbeat_if_due();
+                self.expire_dumps();
+                self.schedule_tick();
+                false
+            }
+            Event::ModeUpdate(mode_info) => {
+                self.session_name = mode_info.session_name;
+                let was_scrolling = self.scrolling();
+                self.input_mode = mode_info.mode;
+                if self.scrolling() != was_scrolling && self.manifest_seen && !self.paused {
+                    self.export_to_file();
+                }
+                was_scrolling != self.scrolling()
+            }
+            Event::Key(key) => self.handle_key(key),
+            Event::PermissionRequestResult(status) => {
+                self.log.info(format!("permissions {:?}", status));
+                self.permissions = Some(status);
                 true
             }
+            Event::TabUpdate(tabs) => {
+                let before = (self.active_tab, self.client_tabs.clone());
+                self.update_tabs(tabs);
+                self.track_focus();
+                let changed = (self.active_tab, self.client_tabs.clone()) != before;
+                if changed && self.manifest_seen && !self.paused {
+                    self.export_to_file();
+                }
+                true
+            }
+            Event::CommandPaneOpened(terminal_id, context) => {
+                match context.get(PANE_ROLE).map(|r| r.as_str()) {
+                    Some("preview") => {
+                        self.preview_pane = Some(terminal_id);
+                        // Keep the keyboard on the tracker so the list stays navigable
+                        focus_plugin_pane(get_plugin_ids().plugin_id, false);
+                    }
+                    Some("spawn") => self.name_spawned_pane(terminal_id, &context),
+                    _ => {}
+                }
+                false
+            }
+            Event::RunCommandResult(exit_code, stdout, stderr, context) => {
+                match self
+                    .commands
+                    .handle_result(exit_code, stdout, &stderr, &context)
+                {
+                    Some(outcome) => self.handle_command_outcome(outcome),
+                    None => false,
+                }
+            }
             _ => false,
-        }
+        };
+        self.flush_log();
+        self.end_cycle(cycle);
+        should_render
     }
 
-    fn render(&mut self, _rows: usize, _cols: usize) {
-        println!("Pane Tracker");
-        println!("============");
-        println!("Tracking {} panes", self.pane_names.len());
-        println!();
-        println!("Export: /tmp/zj-pane-names.json");
-        println!();
-        println!("Panes:");
-        for (pane_id, name) in &self.pane_names {
-            let cmd = self.pane_commands.get(pane_id)
-                .map(|s| s.as_str())
-                .unwrap_or("-");
-            println!("  {} -> {} ({})", pane_id, name, cmd);
-        }
+    fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
+        self.begin_cycle();
+        let should_render = self.handle_pipe(pipe_message);
+        self.flush_log();
+        self.end_cycle("pipe");
+        should_render
+    }
+
+    fn render(&mut self, rows: usize, cols: usize) {
+        self.draw(rows, cols);
     }
 }
 
 impl State {
-    /// Extract pane information from the manifest
+    /// Whether the user is in scroll or search mode, reading the focused
+    /// pane's scrollback. Zellij doesn't report scroll offsets, so a pane
+    /// left scrolled up after returning to normal mode can't be told apart.
+    fn scrolling(&self) -> bool {
+        matches!(
+            self.input_mode,
+            InputMode::Scroll | InputMode::Search | InputMode::EnterSearch
+        )
+    }
+
+    /// Whether the user is looking at this pane's scrollback rather than its
+    /// latest output
+    pub(crate) fn is_scrolled(&self, pane: &TrackedPane) -> bool {
+        pane.info.is_focused && !pane.info.is_plugin && self.scrolling()
+    }
+
+    /// Record tab names and which tab each client has focused
+    fn update_tabs(&mut self, tabs: Vec<TabInfo>) {
+        self.active_tab = tabs.iter().find(|tab| tab.active).map(|tab| tab.position);
+        self.client_tabs.clear();
+        if let Some(position) = self.active_tab {
+            self.client_tabs
+                .insert(get_plugin_ids().client_id, position);
+        }
+        for tab in &tabs {
+            for client in &tab.other_focused_clients {
+                self.client_tabs.insert(*client, tab.position);
+            }
+        }
+        self.tab_names = tabs
+            .into_iter()
+            .map(|tab| (tab.position, tab.name))
+            .collect();
+    }
+
+    /// Bring the tracked panes in line with the manifest, updating panes in
+    /// place so the previous state is there to diff against
     fn update_pane_info(&mut self, manifest: &PaneManifest) {
-        self.pane_names.clear();
-        self.pane_commands.clear();
-        
-        for (_tab_index, panes_in_tab) in &manifest.panes {
+        let mut diff = ManifestDiff::default();
+        let mut seen = BTreeSet::new();
+        let now = unix_now();
+
+        for (tab, panes_in_tab) in &manifest.panes {
             for pane_info in panes_in_tab {
                 let pane_id = if pane_info.is_plugin {
                     format!("plugin_{}", pane_info.id)
                 } else {
                     format!("terminal_{}", pane_info.id)
                 };
-                
-                self.pane_names.insert(pane_id.clone(), pane_info.title.clone());
-                
-                if let Some(ref cmd) = pane_info.terminal_command {
-                    self.pane_commands.insert(pane_id, cmd.clone());
+
+                let category = category::categorize(
+                    &self.config.category_rules,
+                    &pane_info.title,
+                    pane_info.terminal_command.as_deref(),
+                )
+                .map(|rule| rule.name.clone());
+                let icon = self
+                    .config
+                    .icons
+                    .lookup(&pane_info.title, pane_info.terminal_command.as_deref())
+                    .map(str::to_string);
+                let line = pane_info
+                    .terminal_command
+                    .as_deref()
+                    .unwrap_or(&pane_info.title);
+                let remote_host = remote::remote_host(line);
+                let container = remote::container(line);
+                let floating = pane_info
+                    .is_floating
+                    .then(|| FloatingGeometry::of(pane_info, now));
+
+                let watcher = pane_info
+                    .terminal_command
+                    .as_deref()
+                    .and_then(|command| {
+                        watch::matching_watcher(&self.config.command_watchers, command)
+                    })
+                    .map(str::to_string);
+
+                match self.panes.get_mut(&pane_id) {
+                    Some(pane) => {
+                        // A new command, or the same one run again from its exited pane
+                        let started = pane.info.terminal_command != pane_info.terminal_command
+                            || pane.info.exited && !pane_info.exited;
+                        if let Some(watcher) = watcher.filter(|_| started) {
+                            diff.commands.push((pane_id.clone(), watcher));
+                        }
+                        if pane.info.title != pane_info.title {
+                            diff.renamed
+                                .push((pane_id.clone(), pane.info.title.clone()));
+                        }
+                        let was_failed = pane.failed();
+                        pane.tab = *tab;
+                        pane.info = pane_info.clone();
+                        pane.category = category;
+                        pane.icon = icon;
+                        pane.remote_host = remote_host;
+                        pane.container = container;
+                        pane.floating = match (pane.floating.take(), floating) {
+                            (Some(before), Some(current)) if before.same_place(&current) => {
+                                Some(before)
+                            }
+                            (Some(_), Some(current)) => {
+                                self.log.debug(format!(
+                                    "{} moved to {},{} ({}x{})",
+                                    pane_id, current.x, current.y, current.columns, current.rows
+                                ));
+                                Some(current)
+                            }
+                            (_, current) => current,
+                        };
+                        if pane.failed() && !was_failed {
+                            diff.failed.push(pane_id.clone());
+                        }
+                    }
+                    None => {
+                        diff.added.push(pane_id.clone());
+                        if let Some(watcher) = watcher {
+                            diff.commands.push((pane_id.clone(), watcher));
+                        }
+                        self.panes.insert(
+                            pane_id.clone(),
+                            TrackedPane {
+                                tab: *tab,
+                                info: pane_info.clone(),
+                                category,
+                                icon,
+                                remote_host,
+                                container,
+                                logical_id: String::new(),
+                                floating,
+                            },
+                        );
+                    }
                 }
+                seen.insert(pane_id);
             }
         }
+        let gone: Vec<String> = self
+            .panes
+            .keys()
+            .filter(|id| !seen.contains(*id))
+            .cloned()
+            .collect();
+        for pane_id in gone {
+            if let Some(pane) = self.panes.remove(&pane_id) {
+                diff.removed.push((pane_id, pane));
+            }
+        }
+
+        // The first manifest lists panes that already existed at load
+        if self.manifest_seen && !diff.is_empty() {
+            self.log.debug(format!(
+                "manifest: {} added, {} removed, {} renamed, {} failed, {} watched commands",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.renamed.len(),
+                diff.failed.len(),
+                diff.commands.len()
+            ));
+            self.run_pane_hooks(&diff);
+        }
+        self.manifest_seen = true;
+        self.prune_acknowledged();
+        self.track_focus();
+
+        for (pane_id, _) in &diff.removed {
+            self.marked.remove(pane_id);
+            self.tags.remove(pane_id);
+        }
+        for id in diff.removed_terminals() {
+            self.pane_content.remove(&id);
+            self.last_changed.remove(&id);
+            self.content_stats.remove(&id);
+            self.truncated.remove(&id);
+            self.spawned_cwd.remove(&id);
+            self.spawned_by.remove(&id);
+            self.git_info.remove(&id);
+            self.auto_named.remove(&id);
+            self.search_hits.retain(|hit| *hit != id);
+        }
+        self.assign_logical_ids();
+        self.auto_name_panes();
     }
 
     /// Export pane metadata to JSON file
-    fn export_to_file(&self) {
-        let export = PaneNamesExport {
-            panes: self.pane_names.clone(),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs(),
-        };
+    fn export_to_file(&mut self) {
+        let serialize = self.start_phase();
+        self.export_due = false;
+        let path = self.config.names_path();
+        self.stats.commands_failed = self.commands.failures;
+        let export = PaneNamesExport::new(self, unix_now());
+
+        let mut last_write = None;
+        if self.unchanged(&path, export.digest()) {
+            self.log
+                .debug("export: nothing changed, skipping the names file");
+        } else if self.write_names(&path, &export) {
+            last_write = Some(CommandKind::Export);
+        }
+        if self.export_changes() {
+            last_write = Some(CommandKind::Changes);
+        }
+        if self.config.export_layout {
+            let diagram = layout::render_diagram(&self.panes);
+            let path = self.config.layout_path();
+            if !self.unchanged(&path, identity::fnv1a(diagram.as_bytes())) {
+                self.write_file(CommandKind::Layout, &path, &diagram);
+                last_write = Some(CommandKind::Layout);
+            }
+        }
+        if self.config.export_env {
+            let env = envfile::render_env(self);
+            let path = self.config.env_path();
+            if !self.unchanged(&path, identity::fnv1a(env.as_bytes())) {
+                self.write_file(CommandKind::Env, &path, &env);
+                last_write = Some(CommandKind::Env);
+            }
+        }
+        if self.config.export_dot {
+            let graph = dot::render_dot(self);
+            let path = self.config.dot_path();
+            if !self.unchanged(&path, identity::fnv1a(graph.as_bytes())) {
+                self.write_file(CommandKind::Dot, &path, &graph);
+                last_write = Some(CommandKind::Dot);
+            }
+        }
+        self.end_phase(Phase::Serialize, serialize);
+        match last_write {
+            // Written directly, or only previewed under `dry_run`
+            Some(_) if self.config.direct_io() || self.commands.dry_run => self.after_export(),
+            Some(kind) => self.export_awaited = Some(kind),
+            None => {}
+        }
+    }
+
+    /// Steps that need the cycle's exports on disk: the snapshot commit,
+    /// the `zj-changed` marker and `upload_cmd`
+    fn after_export(&mut self) {
+        if self.config.git_snapshots {
+            self.snapshot_commit();
+        }
+        if let Some(marker) = self.pending_marker.take() {
+            let path = self.config.marker_path();
+            self.write_file(CommandKind::Marker, &path, &marker);
+        }
+        if let Some(ref upload_cmd) = self.config.upload_cmd {
+            let env = [
+                ("ZJ_EXPORT_DIR", self.host_path(&self.config.export_dir)),
+                ("ZJ_NAMES_FILE", self.host_path(&self.config.names_path())),
+                (
+                    "ZJ_CHANGES_FILE",
+                    self.host_path(&self.config.changes_path()),
+                ),
+                ("ZJ_SESSION", self.session_name.clone().unwrap_or_default()),
+            ];
+            let argv = self.config.shell.cmd_with_env(upload_cmd, &env);
+            self.run_latest(CommandKind::Upload, &argv);
+        }
+    }
+
+    /// Record `digest` as the contents of the export file at `path`, returning
+    /// whether it was already what the file holds
+    fn unchanged(&mut self, path: &str, digest: u64) -> bool {
+        self.export_digests.insert(path.to_string(), digest) == Some(digest)
+    }
+
+    /// Serialize and write the names export, returning whether it was written
+    fn write_names(&mut self, path: &str, export: &PaneNamesExport) -> bool {
+        match self.config.export_format {
+            ExportFormat::Json => match serde_json::to_string_pretty(&export) {
+                Ok(json) => {
+                    self.log.debug(format!(
+                        "export: writing {} panes to {}",
+                        export.panes.len(),
+                        path
+                    ));
+                    self.export_started = Some((Instant::now(), json.len()));
+                    self.write_file(CommandKind::Export, path, &json);
+                    true
+                }
+                Err(e) => {
+                    self.log
+                        .error(format!("export: failed to serialize: {}", e));
+                    false
+                }
+            },
+            ExportFormat::Msgpack => match rmp_serde::to_vec_named(&export) {
+                Ok(bytes) => {
+                    self.log.debug(format!(
+                        "export: writing {} panes to {} as msgpack",
+                        export.panes.len(),
+                        path
+                    ));
+                    self.export_started = Some((Instant::now(), bytes.len()));
+                    self.write_binary_file(CommandKind::Export, path, &bytes);
+                    true
+                }
+                Err(e) => {
+                    self.log
+                        .error(format!("export: failed to serialize: {}", e));
+                    false
+                }
+            },
+        }
+    }
+
+    /// Write the panes that were added, removed, renamed, refocused or had
+    /// new content since the previous export, returning whether anything
+    /// was written
+    fn export_changes(&mut self) -> bool {
+        let current = PaneSnapshot::of(&self.panes);
+        let changes = ChangesExport::new(
+            &self.exported_panes,
+            &current,
+            &self.content_changed,
+            &self.config.pane_id_format,
+            unix_now(),
+        );
+        self.exported_panes = current;
+        self.content_changed.clear();
+        if changes.is_empty() {
+            // The previous change set still describes the latest write
+            return false;
+        }
+        if self.config.change_marker {
+            let ids: Vec<&str> = changes
+                .changed_ids()
+                .into_iter()
+                .map(String::as_str)
+                .collect();
+            self.pending_marker = Some(format!("{}\n", ids.join("\n")));
+        }
+        match serde_json::to_string_pretty(&changes) {
+            Ok(json) => {
+                let path = self.config.changes_path();
+                self.write_file(CommandKind::Changes, &path, &json);
+                true
+            }
+            Err(e) => {
+                self.log
+                    .error(format!("changes: failed to serialize: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Commit the export directory to its git repository
+    fn snapshot_commit(&mut self) {
+        let message = format!("snapshot {}", unix_now());
+        let dir = self.host_path(&self.config.export_dir);
+        let argv = git::snapshot_commit_cmd(&self.config.shell, &dir, &message);
+        self.run_latest(CommandKind::SnapshotCommit, &argv);
+    }
+
+    /// Pause or resume all capture and export activity. Resuming exports
+    /// right away so the names JSON catches up with changes made meanwhile.
+    fn set_paused(&mut self, paused: bool) {
+        if self.paused == paused {
+            return;
+        }
+        self.paused = paused;
+        self.log.info(if paused {
+            "tracking paused"
+        } else {
+            "tracking resumed"
+        });
+        self.status = Some(
+            if paused {
+                "Tracking paused"
+            } else {
+                "Tracking resumed"
+            }
+            .to_string(),
+        );
+        self.save_state();
+        if !paused {
+            self.export_to_file();
+        }
+    }
 
-        if let Ok(json) = serde_json::to_string_pretty(&export) {
-            let escaped = json
-                .replace('\\', "\\\\")
-                .replace('"', "\\\"")
-                .replace('$', "\\$")
-                .replace('`', "\\`");
-            
-            run_command(
-                &["sh", "-c", &format!("printf '%s' \"{}\" > /tmp/zj-pane-names.json", escaped)],
-                BTreeMap::new(),
+    /// Read the redaction rules and saved state from the export directory
+    /// and write the schema next to the exports. Runs at load and again
+    /// when the config file moves `export_dir`.
+    pub(crate) fn read_export_dir_files(&mut self) {
+        let rules_path = self.config.redact_rules_path();
+        if self.config.direct_io() {
+            let rules = std::fs::read_to_string(&rules_path).unwrap_or_default();
+            self.handle_command_outcome(CommandOutcome {
+                kind: CommandKind::ReadRedactRules,
+                stdout: rules.into_bytes(),
+                error: None,
+            });
+            let state = std::fs::read_to_string(self.config.state_path()).unwrap_or_default();
+            self.restore_state(&state);
+        } else {
+            self.run_latest(
+                CommandKind::ReadRedactRules,
+                &host::read_file_cmd(&rules_path),
             );
+            self.read_state_file();
+        }
+        let schema_path = self.config.schema_path();
+        self.write_file(CommandKind::Schema, &schema_path, export::NAMES_SCHEMA);
+    }
+
+    /// Arm the timer for the next capture/heartbeat check, at the granularity
+    /// of the shortest enabled interval
+    pub(crate) fn schedule_tick(&self) {
+        let capture = self
+            .config
+            .capture_enabled()
+            .then(|| CaptureScheduler::tick(&self.config));
+        let heartbeat =
+            (self.config.heartbeat_interval > 0).then_some(self.config.heartbeat_interval as f64);
+        if let Some(secs) = capture.into_iter().chain(heartbeat).reduce(f64::min) {
+            set_timeout(secs);
+        }
+    }
+
+    /// Write the heartbeat file once `heartbeat_interval` has passed, so
+    /// monitors can tell the plugin is alive even when no panes change
+    fn write_heartbeat_if_due(&mut self) {
+        let interval = self.config.heartbeat_interval;
+        let now = unix_now();
+        if interval == 0 || now.saturating_sub(self.last_heartbeat) < interval {
+            return;
+        }
+        self.last_heartbeat = now;
+        let heartbeat = HeartbeatExport {
+            timestamp: now,
+            session: self.session_name.clone(),
+            pane_count: self.panes.len(),
+            paused: self.paused,
+        };
+        match serde_json::to_string(&heartbeat) {
+            Ok(json) => {
+                let path = self.config.heartbeat_path();
+                self.write_file(CommandKind::Heartbeat, &path, &json);
+            }
+            Err(e) => self
+                .log
+                .error(format!("heartbeat: failed to serialize: {}", e)),
+        }
+    }
+
+    /// Dump every terminal pane whose capture interval has elapsed
+    fn capture_due_panes(&mut self) {
+        let candidates: Vec<CaptureCandidate> = self
+            .panes
+            .iter()
+            .filter_map(|(pane_id, pane)| {
+                Some(CaptureCandidate {
+                    terminal_id: terminal_id(pane_id)?,
+                    name: &pane.info.title,
+                    command: pane.info.terminal_command.as_deref(),
+                })
+            })
+            .collect();
+        let due = self
+            .captures
+            .take_due(&self.config, &candidates, unix_now());
+
+        for id in due {
+            self.capture_pane(id);
+            self.refresh_git_info(id);
+        }
+    }
+
+    /// Route a finished command's result. Returns whether to re-render.
+    fn handle_command_outcome(&mut self, outcome: CommandOutcome) -> bool {
+        if let Some(error) = outcome.error {
+            // Not being in a repository is the common case, not a failure
+            if let CommandKind::GitInfo(id) = outcome.kind {
+                self.git_info.remove(&id);
+                return false;
+            }
+            // Neither is the file not existing yet
+            if outcome.kind == CommandKind::ReadState {
+                self.state_read = true;
+            }
+            if matches!(
+                outcome.kind,
+                CommandKind::ReadState | CommandKind::ReadRedactRules | CommandKind::ReadConfigFile
+            ) {
+                return false;
+            }
+            // Write the file again next cycle even if nothing changes
+            if matches!(
+                outcome.kind,
+                CommandKind::Export | CommandKind::Layout | CommandKind::Dot | CommandKind::Env
+            ) {
+                self.export_digests.clear();
+            }
+            if let CommandKind::Dump(id) | CommandKind::DumpWrite(id) = outcome.kind {
+                self.finish_dump(id, Err(error.clone()));
+            }
+            if outcome.kind == CommandKind::ReadNames {
+                self.status = Some(format!("apply-names: {}", error));
+            }
+            // A failing log write would otherwise log itself forever
+            if outcome.kind != CommandKind::Log {
+                self.log.error(format!("command failed: {}", error));
+            }
+            self.stats.commands_failed = self.commands.failures;
+            return true;
+        }
+        self.log
+            .debug(format!("command finished: {}", outcome.kind));
+        if self.export_awaited.as_ref() == Some(&outcome.kind) {
+            self.export_awaited = None;
+            self.after_export();
+        }
+
+        match outcome.kind {
+            CommandKind::Capture(id) => {
+                let raw = String::from_utf8_lossy(&outcome.stdout);
+                let raw = if self.capture_format(id).ansi {
+                    raw
+                } else {
+                    capture::strip_ansi(&raw).into_owned().into()
+                };
+                let mut content = self.config.redactor.redact(&raw).into_owned();
+                let was_truncated = self.truncated.contains(&id);
+                match capture::truncate_tail(&content, self.config.max_capture_bytes) {
+                    Some(tail) => {
+                        if !was_truncated {
+                            self.log.info(format!(
+                                "terminal_{}: capture is {} bytes, keeping the last {}",
+                                id,
+                                content.len(),
+                                self.config.max_capture_bytes
+                            ));
+                        }
+                        content = tail.to_string();
+                        self.truncated.insert(id);
+                    }
+                    None => {
+                        self.truncated.remove(&id);
+                    }
+                }
+                let previous = self.pane_content.get(&id).map(String::as_str);
+                let stats =
+                    ContentStats::compute(previous, &content, &self.config.error_line_pattern);
+                let stats_changed = self.content_stats.get(&id) != Some(&stats);
+                self.content_stats.insert(id, stats);
+                let changed = self.pane_content.get(&id) != Some(&content);
+                if changed {
+                    self.content_changed.insert(id);
+                    self.last_changed.insert(id, unix_now());
+                }
+                if changed || stats_changed || was_truncated != self.truncated.contains(&id) {
+                    self.export_due = true;
+                }
+                if let Some(factor) =
+                    self.captures
+                        .record(id, changed, self.config.capture_backoff_max)
+                {
+                    self.log
+                        .info(format!("terminal_{}: capture interval now x{}", id, factor));
+                }
+                if self
+                    .captures
+                    .spend_bytes(&self.config, content.len(), unix_now())
+                {
+                    self.write_capture(id, &content);
+                } else {
+                    self.log.debug(format!(
+                        "terminal_{}: capture not written, max_capture_bytes_per_minute reached",
+                        id
+                    ));
+                }
+                self.pane_content.insert(id, content);
+                false
+            }
+            CommandKind::WriteCapture(id) => {
+                self.stats.captures += 1;
+                if let Some(content) = self.pane_content.get(&id) {
+                    self.stats.bytes_written += content.len() as u64;
+                }
+                true
+            }
+            CommandKind::Export => {
+                self.stats.exports += 1;
+                if let Some((started, bytes)) = self.export_started.take() {
+                    self.stats.last_export_ms = Some(started.elapsed().as_millis() as u64);
+                    self.stats.bytes_written += bytes as u64;
+                }
+                true
+            }
+            CommandKind::Clipboard(id) => {
+                self.status = Some(format!("Copied terminal_{} to clipboard", id));
+                true
+            }
+            CommandKind::GitInfo(id) => {
+                match GitInfo::parse(&String::from_utf8_lossy(&outcome.stdout)) {
+                    Some(git) => self.git_info.insert(id, git),
+                    None => self.git_info.remove(&id),
+                };
+                false
+            }
+            CommandKind::Dump(id) => {
+                self.dump_captured(id, &outcome.stdout);
+                false
+            }
+            CommandKind::DumpWrite(id) => {
+                self.dump_written(id);
+                false
+            }
+            CommandKind::ReadNames => {
+                self.apply_names(&outcome.stdout);
+                true
+            }
+            CommandKind::ReadState => {
+                self.restore_state(&String::from_utf8_lossy(&outcome.stdout));
+                true
+            }
+            CommandKind::Hostname => {
+                let hostname = String::from_utf8_lossy(&outcome.stdout).trim().to_string();
+                self.producer.hostname = Some(hostname).filter(|h| !h.is_empty());
+                false
+            }
+            CommandKind::SnapshotLog(id) => {
+                self.show_snapshots(id, &String::from_utf8_lossy(&outcome.stdout));
+                true
+            }
+            CommandKind::SnapshotDiff(id) => {
+                self.show_diff(id, &String::from_utf8_lossy(&outcome.stdout));
+                true
+            }
+            CommandKind::ReadConfigFile => {
+                self.apply_config_file(&String::from_utf8_lossy(&outcome.stdout));
+                true
+            }
+            CommandKind::ReadRedactRules => {
+                let text = String::from_utf8_lossy(&outcome.stdout);
+                self.redact_rules_text = text.to_string();
+                let errors = self.config.redactor.add_rules_file(&text);
+                for error in errors {
+                    self.log.warn(format!("redaction rules file: {}", error));
+                }
+                if !text.trim().is_empty() {
+                    self.log.info(format!(
+                        "loaded redaction rules from {}",
+                        self.config.redact_rules_path()
+                    ));
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// File a terminal pane's content is captured to, named after its repo
+    /// and branch when `git_capture_names` is on and they're known
+    pub(crate) fn capture_path(&self, terminal_id: u32) -> String {
+        let git = self
+            .git_info
+            .get(&terminal_id)
+            .filter(|_| self.config.git_capture_names);
+        let path = match git {
+            Some(git) => self.config.named_capture_path(terminal_id, &git.slug()),
+            None => self.config.capture_path(terminal_id),
+        };
+        if self.capture_format(terminal_id).gzip {
+            format!("{}.gz", path)
+        } else {
+            path
+        }
+    }
+
+    /// `path` as the host sees it. Files written directly land under
+    /// `/host`, which only exists in the sandbox, so host commands and
+    /// anything written for the user get the plugin's cwd in its place.
+    pub(crate) fn host_path(&self, path: &str) -> String {
+        match (
+            Path::new(path).strip_prefix(config::HOST_DIR),
+            &self.host_cwd,
+        ) {
+            (Ok(rest), Some(cwd)) if rest.as_os_str().is_empty() => {
+                cwd.to_string_lossy().into_owned()
+            }
+            (Ok(rest), Some(cwd)) => cwd.join(rest).to_string_lossy().into_owned(),
+            _ => path.to_string(),
+        }
+    }
+
+    /// Shell command that turns a terminal pane's capture file, on stdin,
+    /// back into text. `None` when the file is plain text already, an error
+    /// when it's encrypted and there's no `decrypt_cmd`.
+    pub(crate) fn capture_reader(&self, terminal_id: u32) -> Result<Option<String>, String> {
+        let gzip = self.capture_format(terminal_id).gzip;
+        let decrypt = match (&self.config.encrypt_cmd, &self.config.decrypt_cmd) {
+            (None, _) => None,
+            (Some(_), Some(decrypt)) => Some(decrypt),
+            (Some(_), None) => {
+                return Err("Captures are encrypted and decrypt_cmd isn't set".to_string())
+            }
+        };
+        Ok(match (decrypt, gzip) {
+            (None, false) => None,
+            (None, true) => Some("gzip -dc".to_string()),
+            (Some(decrypt), false) => Some(decrypt.clone()),
+            (Some(decrypt), true) => Some(format!("{} | gzip -dc", decrypt)),
+        })
+    }
+
+    /// Capture format of a terminal pane, from `capture_format_rules` or the
+    /// global `capture_format`
+    pub(crate) fn capture_format(&self, terminal_id: u32) -> CaptureFormat {
+        match self.panes.get(&format!("terminal_{}", terminal_id)) {
+            Some(pane) => self
+                .config
+                .capture_format_for(&pane.info.title, pane.info.terminal_command.as_deref()),
+            None => self.config.capture_format,
+        }
+    }
+
+    /// Queue a dump of a terminal pane in its capture format
+    pub(crate) fn capture_pane(&mut self, terminal_id: u32) {
+        let argv = capture::capture_cmd(terminal_id, self.capture_format(terminal_id));
+        self.run_latest(CommandKind::Capture(terminal_id), &argv);
+    }
+
+    /// Look up the repo and branch of a pane's cwd, if the tracker knows it
+    pub(crate) fn refresh_git_info(&mut self, terminal_id: u32) {
+        if let Some(cwd) = self.spawned_cwd.get(&terminal_id) {
+            let argv = git::git_info_cmd(cwd);
+            self.run_latest(CommandKind::GitInfo(terminal_id), &argv);
+        }
+    }
+
+    /// Write a pane's captured content to its capture file
+    fn write_capture(&mut self, terminal_id: u32, content: &str) {
+        let path = self.capture_path(terminal_id);
+        if !self.capture_format(terminal_id).gzip {
+            self.write_file(CommandKind::WriteCapture(terminal_id), &path, content);
+            return;
+        }
+        let cmd = match self.config.encrypt_cmd {
+            Some(ref encrypt) => format!("gzip -c | {}", encrypt),
+            None => "gzip -c".to_string(),
+        };
+        let argv = self
+            .config
+            .shell
+            .write_file_through_cmd(&cmd, &path, content);
+        self.run_latest(CommandKind::WriteCapture(terminal_id), &argv);
+    }
+
+    /// Replace a file on the host. Everything but the heartbeat and the
+    /// schema, which hold nothing from the session, goes through
+    /// `encrypt_cmd` when it's set. With `minimal_permissions` or
+    /// `direct_writes` the file is
+    /// written directly and the outcome handled right away.
+    fn write_file(&mut self, kind: CommandKind, path: &str, contents: &str) {
+        if self.config.direct_io() {
+            self.write_direct(kind, path, contents.as_bytes());
+            return;
+        }
+        let sensitive = !matches!(kind, CommandKind::Heartbeat | CommandKind::Schema);
+        let argv = match self.config.encrypt_cmd {
+            Some(ref cmd) if sensitive => self
+                .config
+                .shell
+                .write_file_through_cmd(cmd, path, contents),
+            _ => self.config.shell.write_file_cmd(path, contents),
+        };
+        self.run_latest(kind, &argv);
+    }
+
+    /// Write binary `contents` to `path` on the host. Goes through
+    /// `encrypt_cmd` like the other exports.
+    fn write_binary_file(&mut self, kind: CommandKind, path: &str, contents: &[u8]) {
+        if self.config.direct_io() {
+            self.write_direct(kind, path, contents);
+            return;
+        }
+        let encoded = BASE64.encode(contents);
+        let argv = self.config.shell.write_base64_file_cmd(
+            self.config.encrypt_cmd.as_deref(),
+            path,
+            &encoded,
+        );
+        self.run_latest(kind, &argv);
+    }
+
+    /// Append `contents` to the file at `path`, creating it, starting with
+    /// `header` if given, when it doesn't exist or is empty. Encrypted output
+    /// can't be appended to, so under `encrypt_cmd` the file is rewritten
+    /// with what this session appended (its last MiB) instead.
+    fn append_file(&mut self, kind: CommandKind, path: &str, header: Option<&str>, contents: &str) {
+        if self.config.encrypt_cmd.is_some() && !self.config.direct_io() {
+            let header = header.unwrap_or_default();
+            let buffer = self
+                .appended
+                .entry(path.to_string())
+                .or_insert_with(|| header.to_string());
+            buffer.push_str(contents);
+            if let Some(tail) = capture::truncate_tail(buffer, MAX_ENCRYPTED_APPEND_BYTES) {
+                *buffer = format!("{}{}", header, tail);
+            }
+            let whole = buffer.clone();
+            self.write_file(kind, path, &whole);
+            return;
+        }
+        if self.config.direct_io() {
+            if self.commands.dry_run {
+                let what = format!("append {} bytes to {}", contents.len(), path);
+                self.commands.preview(&kind, &what);
+                return;
+            }
+            if is_symlink(path) {
+                self.log
+                    .error(format!("{}: is a symlink, not following it", path));
+                return;
+            }
+            let is_new = std::fs::metadata(path).map_or(true, |meta| meta.len() == 0);
+            let written = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| {
+                    if let Some(header) = header.filter(|_| is_new) {
+                        file.write_all(header.as_bytes())?;
+                    }
+                    file.write_all(contents.as_bytes())
+                });
+            if let Err(e) = written {
+                self.log.error(format!("{}: {}", path, e));
+            }
+            return;
+        }
+        let argv = match header {
+            Some(header) => self
+                .config
+                .shell
+                .append_with_header_cmd(path, header, contents),
+            None => self.config.shell.append_file_cmd(path, contents),
+        };
+        self.commands.run(kind, &argv);
+    }
+
+    /// `minimal_permissions` and `direct_writes` write straight to the
+    /// sandbox filesystem, but never through a symlink. The outcome goes
+    /// through the same handler as a host command's would.
+    fn write_direct(&mut self, kind: CommandKind, path: &str, contents: &[u8]) {
+        if self.commands.dry_run {
+            let what = format!("write {} bytes to {}", contents.len(), path);
+            self.commands.preview(&kind, &what);
+            return;
+        }
+        let written = if is_symlink(path) {
+            Err(std::io::Error::other("is a symlink, not following it"))
+        } else {
+            std::fs::write(path, contents)
+        };
+        let error = written.err().map(|e| format!("[{}] {}: {}", kind, path, e));
+        if let Some(ref error) = error {
+            self.commands.failures += 1;
+            self.commands.last_error = Some(error.clone());
+        }
+        self.handle_command_outcome(CommandOutcome {
+            kind,
+            stdout: Vec::new(),
+            error,
+        });
+    }
+
+    /// Queue a host command, logging the invocation. A still-queued command
+    /// of the same kind is replaced, since only the latest write matters.
+    fn run_latest(&mut self, kind: CommandKind, argv: &[String]) {
+        self.log.debug(format!(
+            "run [{}]: {}",
+            kind,
+            argv[..argv.len().min(3)].join(" ")
+        ));
+        self.commands.run_latest(kind, argv);
+    }
+
+    /// Append buffered log lines to the log file, and under `dry_run` the
+    /// held-back commands to the preview log. The writes themselves aren't
+    /// logged, otherwise every flush would queue another line.
+    fn flush_log(&mut self) {
+        if let Some(previews) = self.commands.take_previews() {
+            let path = self.config.dry_run_path();
+            if self.config.direct_io() {
+                let _ = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .and_then(|mut file| file.write_all(previews.as_bytes()));
+            } else {
+                self.append_file(CommandKind::DryRunLog, &path, None, &previews);
+            }
+        }
+        let Some(lines) = self.log.take_pending() else {
+            return;
+        };
+        if self.config.direct_io() {
+            let _ = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.log.path())
+                .and_then(|mut file| file.write_all(lines.as_bytes()));
+        } else {
+            let argv = self.config.shell.append_file_cmd(self.log.path(), &lines);
+            self.commands.run(CommandKind::Log, &argv);
         }
     }
 }
+
+/// Whether `path` is a symlink. Direct writes don't follow them, since the
+/// target may be outside the sandbox or a link the host can't resolve.
+fn is_symlink(path: &str) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+}
+
+/// Numeric id of a `terminal_N` pane id, `None` for plugin panes
+fn terminal_id(pane_id: &str) -> Option<u32> {
+    pane_id.strip_prefix("terminal_")?.parse().ok()
+}
+
+/// Seconds since the Unix epoch
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Zellij provides this import only to the WASM build. Native builds (tests,
+/// clippy, `cargo build` on the host) need a definition to link against.
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+extern "C" fn host_run_plugin_command() {}
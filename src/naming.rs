@@ -0,0 +1,154 @@
+use crate::category::program_name;
+use crate::git::GitInfo;
+use crate::{terminal_id, State};
+use regex::Regex;
+use std::collections::BTreeMap;
+use zellij_tile::prelude::rename_terminal_pane;
+
+pub const DEFAULT_NAME_TEMPLATE: &str = "{program}:{target}";
+
+const NAME_FIELDS: &[&str] = &["program", "target", "repo", "branch"];
+
+/// Shells name nothing useful, so they aren't auto-named by default
+pub const DEFAULT_NAME_EXCLUDE: &[&str] = &["bash", "zsh", "fish", "sh", "nu"];
+
+/// Titles Zellij assigns by default, like `Pane #3`
+pub const DEFAULT_NAME_PATTERN: &str = r"^Pane #\d+$";
+
+/// Whether a title still matches the configured default-name pattern
+pub fn is_default_name(pattern: &Regex, title: &str) -> bool {
+    pattern.is_match(title)
+}
+
+/// Check that a name template only uses known fields
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in '{}'", template))?;
+        let field = &rest[start + 1..start + end];
+        if !NAME_FIELDS.contains(&field) {
+            return Err(format!("unknown field '{{{}}}' in '{}'", field, template));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Name derived from a pane's command and repository, e.g. `vim:src/lib.rs`
+/// or `cargo:myrepo`. `{target}` is the first non-flag argument, falling
+/// back to the repo name. Separators left dangling by empty fields are
+/// trimmed; `None` when there's no command to go on.
+pub fn suggest_name(
+    template: &str,
+    command: Option<&str>,
+    git: Option<&GitInfo>,
+) -> Option<String> {
+    let command = command?;
+    let program = program_name(command);
+    if program.is_empty() {
+        return None;
+    }
+    let repo = git.map(|g| g.repo.as_str()).unwrap_or_default();
+    let branch = git.map(|g| g.branch.as_str()).unwrap_or_default();
+    let target = command
+        .split_whitespace()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or(repo);
+
+    let name = template
+        .replace("{program}", program)
+        .replace("{target}", target)
+        .replace("{repo}", repo)
+        .replace("{branch}", branch);
+    let name = name.trim_matches(|c: char| c.is_whitespace() || matches!(c, ':' | '-' | '@' | '/'));
+    Some(name.to_string()).filter(|n| !n.is_empty())
+}
+
+impl State {
+    /// Names shared by more than one terminal pane, with those panes' ids.
+    /// Plugin panes are left out: every tab has its own `zellij:tab-bar`.
+    pub(crate) fn duplicate_names(&self) -> BTreeMap<&str, Vec<&String>> {
+        let mut by_name: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+        for (pane_id, pane) in &self.panes {
+            if !pane.info.is_plugin {
+                by_name.entry(&pane.info.title).or_default().push(pane_id);
+            }
+        }
+        by_name.retain(|_, ids| ids.len() > 1);
+        by_name
+    }
+
+    /// Suggested name for a terminal pane still using its default title
+    pub(crate) fn suggested_name(&self, pane_id: &str) -> Option<String> {
+        let pane = self.panes.get(pane_id)?;
+        let id = terminal_id(pane_id)?;
+        if !is_default_name(&self.config.default_name_pattern, &pane.info.title) {
+            return None;
+        }
+        let command = pane.info.terminal_command.as_deref();
+        if command.is_some_and(|c| {
+            self.config
+                .auto_name_exclude
+                .iter()
+                .any(|p| p == program_name(c))
+        }) {
+            return None;
+        }
+        suggest_name(
+            &self.config.auto_name_template,
+            command,
+            self.git_info.get(&id),
+        )
+    }
+
+    /// Rename the selected pane to its suggested name
+    pub(crate) fn accept_suggested_name(&mut self) {
+        let Some(pane_id) = self.selected_pane_id().cloned() else {
+            return;
+        };
+        let (Some(id), Some(name)) = (terminal_id(&pane_id), self.suggested_name(&pane_id)) else {
+            self.status = Some(format!("No name to suggest for {}", pane_id));
+            return;
+        };
+        if self.commands.dry_run {
+            let what = format!("terminal_{} '{}'", id, name);
+            self.commands.preview("rename", &what);
+            self.status = Some(format!("Dry run: not renaming {} to {}", pane_id, name));
+            return;
+        }
+        rename_terminal_pane(id, &name);
+        self.status = Some(format!("Renamed {} to {}", pane_id, name));
+    }
+
+    /// Rename panes that still have a default title, once each
+    pub(crate) fn auto_name_panes(&mut self) {
+        if !self.config.auto_name {
+            return;
+        }
+        let renames: Vec<(u32, String)> = self
+            .panes
+            .keys()
+            .filter_map(|pane_id| {
+                let id = terminal_id(pane_id)?;
+                if self.auto_named.contains(&id) {
+                    return None;
+                }
+                Some((id, self.suggested_name(pane_id)?))
+            })
+            .collect();
+        for (id, name) in renames {
+            self.auto_named.insert(id);
+            if self.commands.dry_run {
+                let what = format!("terminal_{} '{}'", id, name);
+                self.commands.preview("rename", &what);
+                continue;
+            }
+            self.log
+                .info(format!("auto-naming terminal_{} '{}'", id, name));
+            rename_terminal_pane(id, &name);
+        }
+    }
+}
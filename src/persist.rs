@@ -0,0 +1,92 @@
+use crate::commands::CommandKind;
+use crate::host;
+use crate::sets::SetPane;
+use crate::State;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// User-set tracker state that should survive plugin reloads and
+/// detach/reattach, written to `zj-tracker-state.json` whenever it changes
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PersistedState {
+    pub paused: bool,
+    pub hide_unselectable: bool,
+    pub preview_enabled: bool,
+    /// Terminal ids of failed panes whose alert was acknowledged
+    pub acknowledged_alerts: BTreeSet<u32>,
+    /// Pane sets saved with `S` or `save-set`
+    pub pane_sets: BTreeMap<String, Vec<SetPane>>,
+    /// Tags given from the bulk menu, by pane id
+    pub tags: BTreeMap<String, BTreeSet<String>>,
+    /// The first-run setup screen was dismissed
+    pub onboarded: bool,
+}
+
+impl State {
+    /// Write the current user state to the state file
+    pub(crate) fn save_state(&mut self) {
+        let state = PersistedState {
+            paused: self.paused,
+            hide_unselectable: self.hide_unselectable,
+            preview_enabled: self.preview_enabled,
+            acknowledged_alerts: self.acknowledged.clone(),
+            pane_sets: self.pane_sets.clone(),
+            tags: self.tags.clone(),
+            onboarded: self.onboarded,
+        };
+        match serde_json::to_string_pretty(&state) {
+            Ok(json) => {
+                let path = self.config.state_path();
+                self.write_file(CommandKind::SaveState, &path, &json);
+            }
+            Err(e) => self.log.error(format!("state: failed to serialize: {}", e)),
+        }
+    }
+
+    /// Read the state file back, through `decrypt_cmd` when it's written
+    /// encrypted
+    pub(crate) fn read_state_file(&mut self) {
+        let path = self.config.state_path();
+        let argv = match (&self.config.encrypt_cmd, &self.config.decrypt_cmd) {
+            (None, _) => host::read_file_cmd(&path),
+            (Some(_), Some(decrypt)) => self
+                .config
+                .shell
+                .script(&format!("{} < \"$1\"", decrypt), [path]),
+            (Some(_), None) => {
+                self.log.warn(
+                    "state: encrypt_cmd is set without decrypt_cmd, the state file can't be read back",
+                );
+                self.state_read = true;
+                return;
+            }
+        };
+        self.run_latest(CommandKind::ReadState, &argv);
+    }
+
+    /// Apply a state file read at load. An empty file means there's nothing
+    /// to restore yet.
+    pub(crate) fn restore_state(&mut self, json: &str) {
+        self.state_read = true;
+        if json.trim().is_empty() {
+            return;
+        }
+        match serde_json::from_str::<PersistedState>(json) {
+            Ok(state) => {
+                self.paused = state.paused;
+                self.hide_unselectable = state.hide_unselectable;
+                self.preview_enabled = state.preview_enabled;
+                self.acknowledged = state.acknowledged_alerts;
+                self.pane_sets = state.pane_sets;
+                self.tags = state.tags;
+                self.onboarded = state.onboarded;
+                self.log
+                    .info(format!("restored state from {}", self.config.state_path()));
+            }
+            Err(e) => self
+                .log
+                .warn(format!("state: ignoring unreadable state file: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::{fixture, layout, terminal_id, State};
+use zellij_tile::prelude::*;
+
+/// Commands accepted over `zellij pipe`, e.g. `zellij pipe -p <plugin> -- pause`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipeCommand {
+    Pause,
+    Resume,
+    TogglePause,
+    /// Type `text` into a terminal pane
+    Send {
+        target: String,
+        text: String,
+    },
+    /// Re-read the config file
+    Reload,
+    /// List panes, all of them or one tab's
+    List {
+        tab: Option<String>,
+    },
+    /// Capture terminal panes now, all of them or one tab's
+    Capture {
+        tab: Option<String>,
+    },
+    /// Save a tab's panes, the active tab's by default, as a pane set
+    SaveSet {
+        name: String,
+        tab: Option<String>,
+    },
+    /// Re-create a saved pane set in the current tab
+    OpenSet {
+        name: String,
+    },
+    DeleteSet {
+        name: String,
+    },
+    /// Capture one pane now, to `out` or as the reply
+    Dump {
+        target: String,
+        out: Option<String>,
+    },
+    /// Rename live panes after a saved names export
+    ApplyNames {
+        path: String,
+    },
+    /// Acknowledge one failed pane's alert, or every alert when `None`
+    Ack {
+        target: Option<String>,
+    },
+    /// Reply with the current manifest in the test fixture format
+    Manifest,
+    /// Bundle a context profile's panes, replying with the bundle
+    Bundle {
+        profile: String,
+    },
+    /// Reply with the `benchmark` timings, or clear them
+    Benchmark {
+        reset: bool,
+    },
+}
+
+impl PipeCommand {
+    pub fn parse(payload: &str) -> Result<Self, String> {
+        let args = split_args(payload)?;
+        let Some((name, rest)) = args.split_first() else {
+            return Err("empty command".to_string());
+        };
+        match name.as_str() {
+            "pause" => Ok(PipeCommand::Pause),
+            "resume" => Ok(PipeCommand::Resume),
+            "toggle-pause" => Ok(PipeCommand::TogglePause),
+            "reload" => Ok(PipeCommand::Reload),
+            "send" => match rest {
+                [target, text] => Ok(PipeCommand::Send {
+                    target: target.clone(),
+                    text: text.clone(),
+                }),
+                _ => Err("usage: send <pane> <text>".to_string()),
+            },
+            "list" => Ok(PipeCommand::List {
+                tab: parse_scope(rest, "list")?,
+            }),
+            "capture" => Ok(PipeCommand::Capture {
+                tab: parse_scope(rest, "capture")?,
+            }),
+            "save-set" => match rest.split_first() {
+                Some((name, scope)) => Ok(PipeCommand::SaveSet {
+                    name: name.clone(),
+                    tab: parse_scope(scope, "save-set <name>")?,
+                }),
+                None => Err("usage: save-set <name> [tab=<tab>]".to_string()),
+            },
+            "open-set" => match rest {
+                [name] => Ok(PipeCommand::OpenSet { name: name.clone() }),
+                _ => Err("usage: open-set <name>".to_string()),
+            },
+            "delete-set" => match rest {
+                [name] => Ok(PipeCommand::DeleteSet { name: name.clone() }),
+                _ => Err("usage: delete-set <name>".to_string()),
+            },
+            "dump" => parse_dump(rest),
+            "apply-names" => match rest {
+                [path] => Ok(PipeCommand::ApplyNames { path: path.clone() }),
+                _ => Err("usage: apply-names <file>".to_string()),
+            },
+            "ack" => match rest {
+                [all] if all == "all" => Ok(PipeCommand::Ack { target: None }),
+                [target] => Ok(PipeCommand::Ack {
+                    target: Some(target.clone()),
+                }),
+                _ => Err("usage: ack <pane>|all".to_string()),
+            },
+            "manifest" => Ok(PipeCommand::Manifest),
+            "bundle" => match rest {
+                [profile] => Ok(PipeCommand::Bundle {
+                    profile: profile.clone(),
+                }),
+                _ => Err("usage: bundle <profile>".to_string()),
+            },
+            "benchmark" => match rest {
+                [] => Ok(PipeCommand::Benchmark { reset: false }),
+                [reset] if reset == "reset" => Ok(PipeCommand::Benchmark { reset: true }),
+                _ => Err("usage: benchmark [reset]".to_string()),
+            },
+            other => Err(format!("unknown command '{}'", other)),
+        }
+    }
+}
+
+/// `name=<pane> [out=<file>]`
+fn parse_dump(args: &[String]) -> Result<PipeCommand, String> {
+    let usage = || "usage: dump name=<pane> [out=<file>]".to_string();
+    let mut target = None;
+    let mut out = None;
+    for arg in args {
+        match arg.split_once('=') {
+            Some(("name", value)) if !value.is_empty() => target = Some(value.to_string()),
+            Some(("out", value)) if !value.is_empty() => out = Some(value.to_string()),
+            _ => return Err(usage()),
+        }
+    }
+    Ok(PipeCommand::Dump {
+        target: target.ok_or_else(usage)?,
+        out,
+    })
+}
+
+/// Optional `tab=<position or name>` argument of `list` and `capture`
+fn parse_scope(args: &[String], command: &str) -> Result<Option<String>, String> {
+    match args {
+        [] => Ok(None),
+        [arg] => match arg.strip_prefix("tab=") {
+            Some(tab) if !tab.is_empty() => Ok(Some(tab.to_string())),
+            _ => Err(format!("usage: {} [tab=<tab>]", command)),
+        },
+        _ => Err(format!("usage: {} [tab=<tab>]", command)),
+    }
+}
+
+/// Split a command line into words, honouring single and double quotes.
+/// Inside double quotes `\n`, `\t`, `\"` and `\\` are unescaped.
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => current.push('\n'),
+                            Some('t') => current.push('\t'),
+                            Some('r') => current.push('\r'),
+                            Some(c) => current.push(c),
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+impl State {
+    /// Handle a pipe message, replying on the CLI pipe when it came from one
+    pub(crate) fn handle_pipe(&mut self, message: PipeMessage) -> bool {
+        let Some(payload) = message.payload.as_deref() else {
+            return false;
+        };
+        self.log.debug(format!("pipe: {}", payload));
+        let cli_pipe = match message.source {
+            PipeSource::Cli(ref pipe_id) => Some(pipe_id.clone()),
+            _ => None,
+        };
+        let response = match PipeCommand::parse(payload) {
+            // Answered once the content is written, see `finish_dump`
+            Ok(PipeCommand::Dump { target, out }) => {
+                match self.start_dump(&target, out, cli_pipe) {
+                    Ok(()) => return true,
+                    Err(e) => Err(e),
+                }
+            }
+            parsed => parsed.and_then(|command| self.run_pipe_command(command)),
+        };
+        if let Err(ref e) = response {
+            self.log.warn(format!("pipe: {}", e));
+        }
+
+        if let PipeSource::Cli(ref pipe_id) = message.source {
+            let text = match response {
+                Ok(text) => text,
+                Err(e) => format!("error: {}", e),
+            };
+            cli_pipe_output(pipe_id, &format!("{}\n", text));
+            unblock_cli_pipe_input(pipe_id);
+        }
+        true
+    }
+
+    fn run_pipe_command(&mut self, command: PipeCommand) -> Result<String, String> {
+        match command {
+            PipeCommand::Pause => self.set_paused(true),
+            PipeCommand::Resume => self.set_paused(false),
+            PipeCommand::TogglePause => self.set_paused(!self.paused),
+            PipeCommand::Send { target, text } => {
+                let id = self.resolve_terminal(&target)?;
+                if self.commands.dry_run {
+                    let what = format!("{} bytes to terminal_{}", text.len(), id);
+                    self.commands.preview("send", &what);
+                    return Ok(format!("dry run: would send {}", what));
+                }
+                write_chars_to_pane_id(&text, PaneId::Terminal(id));
+                self.log.info(format!(
+                    "pipe: sent {} bytes to terminal_{}",
+                    text.len(),
+                    id
+                ));
+                return Ok(format!("sent {} bytes to terminal_{}", text.len(), id));
+            }
+            PipeCommand::Reload => {
+                self.read_config_file();
+                let path = self.host_path(&self.config.config_file_path());
+                return Ok(format!("reloading {}", path));
+            }
+            PipeCommand::List { tab } => return self.list_panes(tab.as_deref()),
+            PipeCommand::Capture { tab } => return self.capture_panes(tab.as_deref()),
+            PipeCommand::SaveSet { name, tab } => {
+                let tab = match tab {
+                    Some(tab) => self.resolve_tab(&tab)?,
+                    None => self.active_tab.ok_or("no active tab yet")?,
+                };
+                let count = self.save_pane_set(&name, tab)?;
+                return Ok(format!("saved {} panes as '{}'", count, name));
+            }
+            PipeCommand::OpenSet { name } => {
+                let count = self.open_pane_set(&name)?;
+                return Ok(format!("opening {} panes of '{}'", count, name));
+            }
+            PipeCommand::DeleteSet { name } => {
+                self.delete_pane_set(&name)?;
+                return Ok(format!("deleted '{}'", name));
+            }
+            PipeCommand::Dump { target, out } => {
+                self.start_dump(&target, out, None)?;
+                return Ok(format!("dumping {}", target));
+            }
+            PipeCommand::ApplyNames { path } => {
+                self.read_names_file(&path);
+                return Ok(format!("applying names from {}", path));
+            }
+            PipeCommand::Ack { target: None } => {
+                return Ok(format!("acknowledged {} alerts", self.acknowledge_all()));
+            }
+            PipeCommand::Ack {
+                target: Some(target),
+            } => {
+                let id = self.resolve_terminal(&target)?;
+                self.acknowledge(id)?;
+                return Ok(format!("acknowledged terminal_{}", id));
+            }
+            PipeCommand::Bundle { profile } => return self.bundle_profile(&profile),
+            PipeCommand::Manifest => {
+                return serde_json::to_string_pretty(&fixture::record(&self.panes))
+                    .map_err(|e| e.to_string());
+            }
+            PipeCommand::Benchmark { reset } => {
+                if !self.config.benchmark {
+                    return Err("benchmark is off".to_string());
+                }
+                if reset {
+                    self.stats.benchmark = None;
+                    return Ok("benchmark reset".to_string());
+                }
+                return serde_json::to_string_pretty(&self.stats.benchmark)
+                    .map_err(|e| e.to_string());
+            }
+        }
+        Ok(if self.paused { "paused" } else { "resumed" }.to_string())
+    }
+
+    /// Panes of the session or of one tab, in tab and reading order
+    fn scoped_panes(&self, tab: Option<&str>) -> Result<Vec<String>, String> {
+        let tab = tab.map(|tab| self.resolve_tab(tab)).transpose()?;
+        Ok(layout::tab_order(&self.panes)
+            .into_iter()
+            .filter(|(position, _)| tab.is_none_or(|tab| tab == *position))
+            .flat_map(|(_, panes)| panes)
+            .collect())
+    }
+
+    /// 0-based position of a tab given by its 1-based position or exact name
+    fn resolve_tab(&self, tab: &str) -> Result<usize, String> {
+        if let Ok(number) = tab.parse::<usize>() {
+            let position = number.wrapping_sub(1);
+            if self.panes.values().any(|pane| pane.tab == position) {
+                return Ok(position);
+            }
+        }
+        self.tab_names
+            .iter()
+            .find(|(_, name)| name.as_str() == tab)
+            .map(|(position, _)| *position)
+            .ok_or_else(|| format!("no tab '{}'", tab))
+    }
+
+    /// One `<pane id>\t<tab>\t<name>` line per pane
+    fn list_panes(&self, tab: Option<&str>) -> Result<String, String> {
+        let lines: Vec<String> = self
+            .scoped_panes(tab)?
+            .iter()
+            .map(|pane_id| {
+                let pane = &self.panes[pane_id];
+                format!(
+                    "{}\t{}\t{}",
+                    self.config.pane_id_format.apply(pane_id),
+                    pane.tab + 1,
+                    pane.info.title
+                )
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    /// Queue a capture of every terminal pane in scope, regardless of
+    /// `capture_interval`
+    fn capture_panes(&mut self, tab: Option<&str>) -> Result<String, String> {
+        if self.config.minimal_permissions {
+            return Err(
+                "capturing needs RunCommands, which minimal_permissions leaves out".to_string(),
+            );
+        }
+        let ids: Vec<u32> = self
+            .scoped_panes(tab)?
+            .iter()
+            .filter_map(|pane_id| terminal_id(pane_id))
+            .collect();
+        for id in &ids {
+            self.capture_pane(*id);
+            self.refresh_git_info(*id);
+        }
+        Ok(format!(
+            "capturing {} panes into {}",
+            ids.len(),
+            self.host_path(&self.config.export_dir)
+        ))
+    }
+
+    /// Find a terminal pane by `terminal_N` id, bare number, exported id,
+    /// logical id or exact name
+    pub(crate) fn resolve_terminal(&self, target: &str) -> Result<u32, String> {
+        let by_id = terminal_id(target)
+            .or_else(|| target.parse().ok())
+            .filter(|id| self.panes.contains_key(&format!("terminal_{}", id)));
+        if let Some(id) = by_id {
+            return Ok(id);
+        }
+        let mut matches = self
+            .panes
+            .iter()
+            .filter(|(id, pane)| {
+                pane.logical_id == target
+                    || pane.info.title == target
+                    || self.config.pane_id_format.apply(id) == target
+            })
+            .filter_map(|(id, _)| terminal_id(id));
+        match (matches.next(), matches.next()) {
+            (Some(id), None) => Ok(id),
+            (Some(_), Some(_)) => Err(format!("'{}' matches more than one pane", target)),
+            (None, _) => Err(format!("no terminal pane '{}'", target)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_args_honours_quotes_and_escapes() {
+        assert_eq!(
+            split_args(r#"  rename 3  "build \"main\"\tok" 'a "b" c' x''y "" "#).unwrap(),
+            ["rename", "3", "build \"main\"\tok", "a \"b\" c", "xy", ""]
+        );
+        assert_eq!(split_args("").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            split_args("send 'oops").unwrap_err(),
+            "unterminated single quote"
+        );
+        assert_eq!(
+            split_args(r#"send "oops\"#).unwrap_err(),
+            "unterminated double quote"
+        );
+    }
+}
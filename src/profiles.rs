@@ -0,0 +1,155 @@
+use crate::commands::CommandKind;
+use crate::{layout, State};
+use std::collections::BTreeSet;
+
+/// A named selection of panes to bundle as context, written as
+/// `<name>=tags:[backend,logs], lines:200`. Panes match when they have one
+/// of the `tags` or one of the `categories`; a profile with neither takes
+/// every pane. `lines` keeps only the end of each capture.
+#[derive(Debug, Clone)]
+pub struct ContextProfile {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub categories: Vec<String>,
+    pub lines: Option<usize>,
+}
+
+impl ContextProfile {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, fields) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("'{}': expected <name>=<fields>", spec))?;
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("'{}': invalid profile name '{}'", spec, name));
+        }
+        let mut profile = ContextProfile {
+            name: name.to_string(),
+            tags: Vec::new(),
+            categories: Vec::new(),
+            lines: None,
+        };
+        for field in split_fields(fields) {
+            let (key, value) = field
+                .split_once(':')
+                .ok_or_else(|| format!("'{}': expected <field>:<value>", field))?;
+            match key.trim() {
+                "tags" => profile.tags = parse_list(value),
+                "categories" => profile.categories = parse_list(value),
+                "lines" => {
+                    let lines = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("'{}': invalid line count", field))?;
+                    profile.lines = Some(lines).filter(|n| *n > 0);
+                }
+                other => return Err(format!("'{}': unknown field '{}'", spec, other)),
+            }
+        }
+        Ok(profile)
+    }
+
+    pub fn matches(&self, category: Option<&str>, tags: Option<&BTreeSet<String>>) -> bool {
+        if self.tags.is_empty() && self.categories.is_empty() {
+            return true;
+        }
+        let tagged = tags.is_some_and(|tags| self.tags.iter().any(|tag| tags.contains(tag)));
+        tagged || category.is_some_and(|category| self.categories.iter().any(|c| c == category))
+    }
+}
+
+/// Split on commas outside `[...]`, dropping empty fields
+fn split_fields(fields: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in fields.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&fields[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&fields[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// `[a,b]` or a bare `a`
+fn parse_list(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .unwrap_or(value);
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl State {
+    /// Bundle the panes of a context profile into `zj-bundle-<name>.txt`,
+    /// returning the bundle
+    pub(crate) fn bundle_profile(&mut self, name: &str) -> Result<String, String> {
+        let profile = self
+            .config
+            .context_profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .cloned()
+            .ok_or_else(|| format!("no context profile '{}'", name))?;
+        let targets: Vec<String> = layout::tab_order(&self.panes)
+            .into_values()
+            .flatten()
+            .filter(|id| {
+                let category = self.panes[id].category.as_deref();
+                profile.matches(category, self.tags.get(id))
+            })
+            .collect();
+        let (bundle, included) = self.render_bundle(&targets, profile.lines);
+        if included == 0 {
+            return Err(format!("no captured panes match '{}'", name));
+        }
+        let path = self.config.profile_bundle_path(name);
+        self.write_file(CommandKind::Bundle, &path, &bundle);
+        self.log.info(format!(
+            "bundled {} panes for profile '{}' into {}",
+            included, name, path
+        ));
+        Ok(bundle)
+    }
+
+    /// Bundle the profile at `index` in `context_profiles`, from the picker
+    pub(crate) fn bundle_profile_at(&mut self, index: usize) {
+        let Some(name) = self
+            .config
+            .context_profiles
+            .get(index)
+            .map(|profile| profile.name.clone())
+        else {
+            return;
+        };
+        self.status = Some(match self.bundle_profile(&name) {
+            Ok(_) => format!(
+                "Bundled '{}' into {}",
+                name,
+                self.config.profile_bundle_path(&name)
+            ),
+            Err(e) => e,
+        });
+    }
+}
@@ -0,0 +1,128 @@
+use crate::State;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Daily time windows during which hooks and MQTT events are held back,
+/// e.g. `22:00-07:00; 12:30-13:30`. A window whose end is before its start
+/// runs past midnight.
+#[derive(Debug, Clone, Default)]
+pub struct QuietHours {
+    /// Start and end of each window, in minutes since local midnight
+    windows: Vec<(u32, u32)>,
+}
+
+impl QuietHours {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let windows = spec
+            .split(';')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(|window| {
+                let (start, end) = window
+                    .split_once('-')
+                    .ok_or_else(|| format!("'{}': expected <start>-<end>", window))?;
+                let start = parse_time(start.trim())
+                    .ok_or_else(|| format!("'{}': invalid start time", window))?;
+                let end = parse_time(end.trim())
+                    .ok_or_else(|| format!("'{}': invalid end time", window))?;
+                Ok((start, end))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(QuietHours { windows })
+    }
+
+    /// Whether `timestamp` (seconds since the epoch) falls in a window, with
+    /// the windows read in the time zone `utc_offset` minutes east of UTC
+    pub fn contains(&self, timestamp: u64, utc_offset: i32) -> bool {
+        let minute = local_minute(timestamp, utc_offset);
+        self.windows.iter().any(|&(start, end)| {
+            if start <= end {
+                (start..end).contains(&minute)
+            } else {
+                minute >= start || minute < end
+            }
+        })
+    }
+}
+
+/// Minutes since local midnight at `timestamp`
+fn local_minute(timestamp: u64, utc_offset: i32) -> u32 {
+    let local = (timestamp / 60) as i64 + utc_offset as i64;
+    local.rem_euclid(MINUTES_PER_DAY as i64) as u32
+}
+
+/// `HH:MM` as minutes since midnight
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// `+HH:MM` / `-HH:MM` (or plain `+HH`) as minutes east of UTC
+pub fn parse_utc_offset(offset: &str) -> Option<i32> {
+    let (sign, rest) = match offset.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    ((0..=14).contains(&hours) && (0..60).contains(&minutes))
+        .then_some(sign * (hours * 60 + minutes))
+}
+
+impl State {
+    /// Whether notifications should be held back right now
+    pub(crate) fn in_quiet_hours(&self, now: u64) -> bool {
+        self.config
+            .quiet_hours
+            .contains(now, self.config.utc_offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since the epoch at `HH:MM` UTC on 1970-01-02
+    fn at(hours: u64, minutes: u64) -> u64 {
+        86_400 + hours * 3600 + minutes * 60
+    }
+
+    #[test]
+    fn windows_parse_and_reject_bad_times() {
+        let quiet = QuietHours::parse(" 22:00-07:00; 12:30 - 13:30 ;").unwrap();
+        assert_eq!(
+            quiet.windows,
+            vec![(22 * 60, 7 * 60), (12 * 60 + 30, 13 * 60 + 30)]
+        );
+        assert!(QuietHours::parse("").unwrap().windows.is_empty());
+        assert!(QuietHours::parse("22:00").is_err());
+        assert!(QuietHours::parse("24:00-07:00").is_err());
+        assert!(QuietHours::parse("22:00-07:60").is_err());
+    }
+
+    #[test]
+    fn windows_past_midnight_wrap() {
+        let quiet = QuietHours::parse("22:00-07:00; 12:30-13:30").unwrap();
+        assert!(quiet.contains(at(23, 0), 0));
+        assert!(quiet.contains(at(6, 59), 0));
+        assert!(!quiet.contains(at(7, 0), 0));
+        assert!(quiet.contains(at(12, 30), 0));
+        assert!(!quiet.contains(at(13, 30), 0));
+        assert!(!quiet.contains(at(21, 59), 0));
+    }
+
+    #[test]
+    fn utc_offset_shifts_the_windows() {
+        let quiet = QuietHours::parse("22:00-23:00").unwrap();
+        assert!(quiet.contains(at(20, 30), 120));
+        assert!(quiet.contains(at(1, 30), -180));
+        assert!(!quiet.contains(at(22, 30), 60));
+        assert_eq!(parse_utc_offset("+02:00"), Some(120));
+        assert_eq!(parse_utc_offset("-05:30"), Some(-330));
+        assert_eq!(parse_utc_offset("9"), Some(540));
+        assert_eq!(parse_utc_offset("+15"), None);
+    }
+}
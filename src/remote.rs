@@ -0,0 +1,230 @@
+use crate::category::program_name;
+use serde::Serialize;
+
+/// Programs whose first operand is the host they connect to
+const REMOTE_PROGRAMS: &[&str] = &["ssh", "mosh", "autossh", "et"];
+
+/// ssh options that take a value as the next argument
+const SSH_OPTIONS_WITH_VALUE: &str = "BbcDEeFIiJLlMmOopQRSWw";
+
+/// Host a command line connects to, e.g. `prod-db` for
+/// `ssh -p 2222 deploy@prod-db tail -f app.log`
+pub fn remote_host(line: &str) -> Option<String> {
+    if !REMOTE_PROGRAMS.contains(&program_name(line)) {
+        return None;
+    }
+    let mut args = line.split_whitespace().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            return args.next().and_then(host_of);
+        }
+        if let Some(flags) = arg.strip_prefix('-') {
+            // `-p 22` takes the next argument, `-p22`, `-At` and `--long` don't
+            if flags.len() == 1 && SSH_OPTIONS_WITH_VALUE.contains(flags) {
+                args.next();
+            }
+            continue;
+        }
+        return host_of(arg);
+    }
+    None
+}
+
+/// Host part of an ssh destination: `host`, `user@host`, `host:port` or
+/// `ssh://user@host:port`
+fn host_of(destination: &str) -> Option<String> {
+    let destination = destination.strip_prefix("ssh://").unwrap_or(destination);
+    let host = destination.rsplit('@').next()?;
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next()?,
+        None if host.matches(':').count() == 1 => host.split(':').next()?,
+        None => host,
+    };
+    Some(host.to_string()).filter(|h| !h.is_empty())
+}
+
+/// Container or pod a pane is attached to through `docker`, `podman` or
+/// `kubectl`
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContainerRef {
+    /// `docker`, `podman`, `docker compose` or `kubectl`
+    pub runtime: String,
+    /// Container, compose service or pod (`pod/web-1`, `deploy/web`)
+    pub name: String,
+    /// Kubernetes namespace, when given
+    pub namespace: Option<String>,
+    /// Container inside the pod, when given with `-c`
+    pub container: Option<String>,
+}
+
+/// Options of `exec`/`attach`/`run` that take a value as the next argument
+const CONTAINER_OPTIONS_WITH_VALUE: &[&str] = &[
+    "-e",
+    "--env",
+    "--env-file",
+    "-u",
+    "--user",
+    "-w",
+    "--workdir",
+    "--detach-keys",
+    "--name",
+    "-n",
+    "--namespace",
+    "-c",
+    "--container",
+    "--context",
+    "--kubeconfig",
+    "--pod-running-timeout",
+];
+
+/// Options before the subcommand that take a value as the next argument
+const GLOBAL_OPTIONS_WITH_VALUE: &[&str] = &[
+    "-n",
+    "--namespace",
+    "--context",
+    "--kubeconfig",
+    "--cluster",
+    "-H",
+    "--host",
+    "-c",
+    "--connection",
+    "-f",
+    "--file",
+    "-p",
+    "--project-name",
+];
+
+/// Container a command line runs in, e.g. `api` for
+/// `docker exec -it api bash` or `pod/web-1` in `shop` for
+/// `kubectl exec -n shop pod/web-1 -c app -- sh`
+pub fn container(line: &str) -> Option<ContainerRef> {
+    let runtime = program_name(line);
+    let mut args = line.split_whitespace().skip(1).peekable();
+    let runtime = match runtime {
+        "docker" | "podman" if args.peek() == Some(&"compose") => {
+            args.next();
+            format!("{} compose", runtime)
+        }
+        "docker" | "podman" | "kubectl" => runtime.to_string(),
+        "docker-compose" => "docker compose".to_string(),
+        _ => return None,
+    };
+    let mut found = ContainerRef {
+        runtime,
+        name: String::new(),
+        namespace: None,
+        container: None,
+    };
+    // Skip global options up to the subcommand; kubectl takes `-n` here too
+    let subcommand = loop {
+        let arg = args.next()?;
+        if !arg.starts_with('-') {
+            break arg;
+        }
+        if GLOBAL_OPTIONS_WITH_VALUE.contains(&arg) {
+            let value = args.next().map(str::to_string);
+            if matches!(arg, "-n" | "--namespace") && found.runtime == "kubectl" {
+                found.namespace = value;
+            }
+        }
+    };
+    if !matches!(subcommand, "exec" | "attach" | "run") {
+        return None;
+    }
+
+    let mut run_name = None;
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        let (option, inline) = match arg.split_once('=') {
+            Some((option, value)) if arg.starts_with('-') => (option, Some(value.to_string())),
+            _ => (arg, None),
+        };
+        if !option.starts_with('-') {
+            // What follows the container, pod or image is the command
+            found.name = option.to_string();
+            break;
+        }
+        let value = match inline {
+            Some(value) => Some(value),
+            None if CONTAINER_OPTIONS_WITH_VALUE.contains(&option) => {
+                args.next().map(str::to_string)
+            }
+            None => None,
+        };
+        match option {
+            "-n" | "--namespace" if found.runtime == "kubectl" => found.namespace = value,
+            "-c" | "--container" if found.runtime == "kubectl" => found.container = value,
+            "--name" => run_name = value,
+            _ => {}
+        }
+    }
+    // A named `docker run` container is found by its name, not its image
+    if let Some(name) = run_name.filter(|_| subcommand == "run") {
+        found.name = name;
+    }
+    // Namespace and container may also follow the pod
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        match arg {
+            "-n" | "--namespace" if found.runtime == "kubectl" => {
+                found.namespace = args.next().map(str::to_string)
+            }
+            "-c" | "--container" if found.runtime == "kubectl" => {
+                found.container = args.next().map(str::to_string)
+            }
+            _ => {}
+        }
+    }
+    Some(found).filter(|found| !found.name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_host_skips_options_and_users() {
+        assert_eq!(
+            remote_host("ssh -p 2222 deploy@prod-db tail -f app.log").as_deref(),
+            Some("prod-db")
+        );
+        assert_eq!(remote_host("mosh -A --ssh=ssh box").as_deref(), Some("box"));
+        assert_eq!(
+            remote_host("ssh -p22 ssh://me@host:2200").as_deref(),
+            Some("host")
+        );
+        assert_eq!(remote_host("ssh -- [::1]:22").as_deref(), Some("::1"));
+        assert_eq!(
+            remote_host("/usr/bin/ssh fe80::1").as_deref(),
+            Some("fe80::1")
+        );
+        assert_eq!(remote_host("ssh -v"), None);
+        assert_eq!(remote_host("sshfs host:/ /mnt"), None);
+    }
+
+    #[test]
+    fn container_reads_runtime_namespace_and_name() {
+        assert_eq!(
+            container("kubectl exec -n shop -it pod/web-1 -c app -- sh"),
+            Some(ContainerRef {
+                runtime: "kubectl".to_string(),
+                name: "pod/web-1".to_string(),
+                namespace: Some("shop".to_string()),
+                container: Some("app".to_string()),
+            })
+        );
+        let api = container("docker exec -it -e TERM=xterm -u root api bash").unwrap();
+        assert_eq!((api.runtime.as_str(), api.name.as_str()), ("docker", "api"));
+        let web = container("docker compose exec web sh").unwrap();
+        assert_eq!(
+            (web.runtime.as_str(), web.name.as_str()),
+            ("docker compose", "web")
+        );
+        assert_eq!(container("docker ps"), None);
+        assert_eq!(container("podman exec"), None);
+    }
+}
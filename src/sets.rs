@@ -0,0 +1,139 @@
+use crate::export::FloatingGeometry;
+use crate::pipe::split_args;
+use crate::spawn::open_named_command_pane;
+use crate::{layout, terminal_id, State};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use zellij_tile::prelude::*;
+
+/// Started for saved panes that had no command of their own
+const LOGIN_SHELL: &str = "exec \"${SHELL:-sh}\"";
+
+/// One pane of a saved pane set
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetPane {
+    pub name: String,
+    /// Command line, unset for plain shells
+    pub command: Option<String>,
+    /// Known for panes the tracker opened itself
+    pub cwd: Option<PathBuf>,
+    /// Set for floating panes
+    pub floating: Option<FloatingGeometry>,
+}
+
+impl SetPane {
+    fn argv(&self) -> Result<Vec<String>, String> {
+        match self.command {
+            Some(ref line) => split_args(line),
+            None => Ok(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                LOGIN_SHELL.to_string(),
+            ]),
+        }
+    }
+}
+
+impl State {
+    /// Save the terminal panes of a tab, 0-based, as the set `name`,
+    /// replacing any set of that name. Returns how many panes were saved.
+    pub(crate) fn save_pane_set(&mut self, name: &str, tab: usize) -> Result<usize, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("a pane set needs a name".to_string());
+        }
+        let panes: Vec<SetPane> = layout::tab_order(&self.panes)
+            .remove(&tab)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|pane_id| {
+                let id = terminal_id(pane_id)?;
+                let pane = &self.panes[pane_id];
+                if !pane.is_interactive() {
+                    return None;
+                }
+                Some(SetPane {
+                    name: pane.info.title.clone(),
+                    command: pane.info.terminal_command.clone(),
+                    cwd: self.spawned_cwd.get(&id).cloned(),
+                    floating: pane.floating.clone(),
+                })
+            })
+            .collect();
+        if panes.is_empty() {
+            return Err(format!("tab {} has no terminal panes", tab + 1));
+        }
+        let count = panes.len();
+        self.log
+            .info(format!("saved {} panes as set '{}'", count, name));
+        self.pane_sets.insert(name.to_string(), panes);
+        self.save_state();
+        Ok(count)
+    }
+
+    /// Open every pane of a saved set in the current tab
+    pub(crate) fn open_pane_set(&mut self, name: &str) -> Result<usize, String> {
+        let panes = self
+            .pane_sets
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no pane set '{}'", name))?;
+        let tracker = format!("plugin_{}", get_plugin_ids().plugin_id);
+        for pane in &panes {
+            let argv = pane
+                .argv()
+                .map_err(|e| format!("'{}' in set '{}': {}", pane.name, name, e))?;
+            let coordinates = pane.floating.as_ref().map(|geometry| {
+                FloatingPaneCoordinates::default()
+                    .with_x_fixed(geometry.x)
+                    .with_y_fixed(geometry.y)
+                    .with_width_fixed(geometry.columns)
+                    .with_height_fixed(geometry.rows)
+            });
+            open_named_command_pane(
+                &tracker,
+                &pane.name,
+                &argv,
+                pane.cwd.clone(),
+                pane.floating.is_some(),
+                coordinates,
+            );
+        }
+        self.log
+            .info(format!("opened set '{}' ({} panes)", name, panes.len()));
+        Ok(panes.len())
+    }
+
+    pub(crate) fn delete_pane_set(&mut self, name: &str) -> Result<(), String> {
+        self.pane_sets
+            .remove(name)
+            .ok_or_else(|| format!("no pane set '{}'", name))?;
+        self.save_state();
+        Ok(())
+    }
+
+    /// Save the selected pane's tab under the name typed in the prompt
+    pub(crate) fn save_selected_tab_as_set(&mut self, name: &str) {
+        let Some(tab) = self
+            .selected_pane_id()
+            .map(|pane_id| self.panes[pane_id].tab)
+        else {
+            return;
+        };
+        self.status = Some(match self.save_pane_set(name, tab) {
+            Ok(count) => format!("Saved {} panes as '{}'", count, name.trim()),
+            Err(e) => e,
+        });
+    }
+
+    /// Open the `index`th saved set, in name order
+    pub(crate) fn open_pane_set_at(&mut self, index: usize) {
+        let Some(name) = self.pane_sets.keys().nth(index).cloned() else {
+            return;
+        };
+        self.status = Some(match self.open_pane_set(&name) {
+            Ok(count) => format!("Opening {} panes of '{}'", count, name),
+            Err(e) => e,
+        });
+    }
+}
@@ -0,0 +1,116 @@
+use crate::State;
+use zellij_tile::prelude::*;
+
+/// What each permission the tracker asks for is needed for
+fn enables(permission: &PermissionType) -> &'static str {
+    match permission {
+        PermissionType::ReadApplicationState => "the pane list and every export",
+        PermissionType::RunCommands => "writing exports and captures, hooks, git snapshots",
+        PermissionType::OpenFiles => "opening captures in an editor (e)",
+        PermissionType::OpenTerminalsOrPlugins => {
+            "preview, mirror, re-run, pane templates and sets (p, m, r, N, O)"
+        }
+        PermissionType::ChangeApplicationState => "renaming panes (auto_name, a, apply-names)",
+        PermissionType::WriteToStdin => "typing into panes (b, send)",
+        PermissionType::ReadCliPipes => "zellij pipe commands",
+        _ => "",
+    }
+}
+
+impl State {
+    /// The permissions requested at load, in the order they were asked for
+    pub(crate) fn requested_permissions(&self) -> Vec<PermissionType> {
+        if self.config.minimal_permissions {
+            vec![
+                PermissionType::ReadApplicationState,
+                PermissionType::ReadCliPipes,
+            ]
+        } else {
+            vec![
+                PermissionType::ReadApplicationState,
+                PermissionType::RunCommands,
+                PermissionType::OpenFiles,
+                PermissionType::OpenTerminalsOrPlugins,
+                PermissionType::ChangeApplicationState,
+                PermissionType::WriteToStdin,
+                PermissionType::ReadCliPipes,
+            ]
+        }
+    }
+
+    /// Whether to show the setup screen instead of the pane list: until
+    /// permissions are granted, and on the first run until it's dismissed
+    pub(crate) fn needs_setup(&self) -> bool {
+        match self.permissions {
+            Some(PermissionStatus::Granted) => self.state_read && !self.onboarded,
+            _ => true,
+        }
+    }
+
+    /// Enter leaves the setup screen once permissions are granted
+    pub(crate) fn handle_setup_key(&mut self, key: &KeyWithModifier) -> bool {
+        if key.bare_key != BareKey::Enter || self.permissions != Some(PermissionStatus::Granted) {
+            return false;
+        }
+        self.onboarded = true;
+        self.save_state();
+        true
+    }
+
+    /// Permissions, resolved paths and what won't work, in place of the
+    /// pane list
+    pub(crate) fn draw_setup(&self) {
+        println!("Setup");
+        println!();
+        let (label, color) = match self.permissions {
+            None => ("waiting for an answer", "33"),
+            Some(PermissionStatus::Granted) => ("granted", "32"),
+            Some(PermissionStatus::Denied) => ("denied", "31"),
+        };
+        println!("Permissions (\u{1b}[{}m{}\u{1b}[0m):", color, label);
+        for permission in self.requested_permissions() {
+            println!(
+                "  {:<24} {}",
+                format!("{:?}", permission),
+                enables(&permission)
+            );
+        }
+        println!();
+        println!("Files:");
+        println!("  names      {}", self.host_path(&self.config.names_path()));
+        println!(
+            "  changes    {}",
+            self.host_path(&self.config.changes_path())
+        );
+        if self.config.capture_enabled() {
+            println!(
+                "  captures   {}",
+                self.host_path(&self.config.export_file("zj-pane-<id>.txt"))
+            );
+        }
+        println!("  state      {}", self.host_path(&self.config.state_path()));
+        println!(
+            "  config     {}",
+            self.host_path(&self.config.config_file_path())
+        );
+        println!("  log        {}", self.host_path(&self.config.log_file));
+        println!();
+        if self.config.minimal_permissions {
+            println!("minimal_permissions is on: files are written directly under /host, and");
+            println!(
+                "content capture, hooks, git snapshots, uploads, encryption and MQTT are off."
+            );
+            println!();
+        }
+        match self.permissions {
+            None => println!("Answer the permission prompt in this pane to continue."),
+            Some(PermissionStatus::Denied) => {
+                println!("Nothing works without these permissions. Zellij remembers the answer in");
+                println!(
+                    "~/.cache/zellij/permissions.kdl: remove this plugin's entry and reload it."
+                );
+            }
+            Some(PermissionStatus::Granted) => println!("Enter: continue to the pane list"),
+        }
+    }
+}
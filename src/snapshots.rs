@@ -0,0 +1,236 @@
+use crate::commands::CommandKind;
+use crate::stats;
+use crate::ui::UiMode;
+use crate::State;
+use std::collections::BTreeMap;
+use zellij_tile::prelude::*;
+
+/// How many snapshots of a pane the picker offers, newest first
+const MAX_SNAPSHOTS: usize = 9;
+
+/// A commit of the `git_snapshots` repository that changed a pane's capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub commit: String,
+    /// Commit time, seconds since the epoch
+    pub time: u64,
+}
+
+impl Snapshot {
+    /// Parse `git log --format='%h %ct'` output
+    pub fn parse_log(stdout: &str) -> Vec<Snapshot> {
+        stdout
+            .lines()
+            .filter_map(|line| {
+                let (commit, time) = line.trim().split_once(' ')?;
+                Some(Snapshot {
+                    commit: commit.to_string(),
+                    time: time.parse().ok()?,
+                })
+            })
+            .collect()
+    }
+
+    /// How long ago the snapshot was taken, e.g. `12m ago`
+    pub fn age(&self, now: u64) -> String {
+        format!("{} ago", stats::format_age(self.time, now))
+    }
+}
+
+/// A diff between two snapshots of a pane, shown in place of the pane list
+pub struct DiffView {
+    pub terminal_id: u32,
+    /// Older side of the diff
+    pub from: String,
+    /// Newer side, or `None` for the capture file as it is now
+    pub to: Option<String>,
+    pub text: String,
+    /// First line shown
+    pub scroll: usize,
+}
+
+impl DiffView {
+    /// `from..to` as shown in the header
+    pub fn range(&self) -> String {
+        format!("{}..{}", self.from, self.to.as_deref().unwrap_or("now"))
+    }
+}
+
+/// Argv of a `git diff` of `file` in the snapshot repository at `dir`
+fn diff_args(dir: &str, from: &str, to: Option<&str>, file: &str, color: bool) -> Vec<String> {
+    let mut argv = vec![
+        "git".to_string(),
+        "-C".to_string(),
+        dir.to_string(),
+        "diff".to_string(),
+        if color { "--color" } else { "--no-color" }.to_string(),
+        from.to_string(),
+    ];
+    argv.extend(to.map(str::to_string));
+    argv.extend(["--".to_string(), file.to_string()]);
+    argv
+}
+
+impl State {
+    /// Capture file of a terminal pane relative to the export directory,
+    /// which is how the snapshot repository knows it
+    fn snapshot_file(&self, terminal_id: u32) -> String {
+        let path = self.capture_path(terminal_id);
+        match path.rsplit_once('/') {
+            Some((_, name)) => name.to_string(),
+            None => path,
+        }
+    }
+
+    /// List the snapshots of the selected pane's capture to pick from
+    pub(crate) fn compare_snapshots(&mut self) {
+        if !self.config.git_snapshots {
+            self.status = Some("Comparing snapshots needs git_snapshots".to_string());
+            return;
+        }
+        let Some((id, _)) = self.selected_capture() else {
+            return;
+        };
+        if !self.plain_capture(id, "Comparing snapshots") {
+            return;
+        }
+        let argv = vec![
+            "git".to_string(),
+            "-C".to_string(),
+            self.host_path(&self.config.export_dir),
+            "log".to_string(),
+            format!("-n{}", MAX_SNAPSHOTS),
+            "--format=%h %ct".to_string(),
+            "--".to_string(),
+            self.snapshot_file(id),
+        ];
+        self.run_latest(CommandKind::SnapshotLog(id), &argv);
+        self.status = Some(format!("Loading snapshots of terminal_{}", id));
+    }
+
+    /// Show the picker once the snapshot list arrives
+    pub(crate) fn show_snapshots(&mut self, terminal_id: u32, stdout: &str) {
+        let snapshots = Snapshot::parse_log(stdout);
+        if snapshots.is_empty() {
+            self.status = Some(format!("No snapshots of terminal_{} yet", terminal_id));
+            return;
+        }
+        self.status = None;
+        self.mode = UiMode::Snapshots {
+            terminal_id,
+            snapshots,
+            from: None,
+        };
+    }
+
+    /// Diff two picked snapshots, older first, or one against the capture as
+    /// it is now
+    pub(crate) fn diff_snapshots(
+        &mut self,
+        terminal_id: u32,
+        from: &Snapshot,
+        to: Option<&Snapshot>,
+    ) {
+        let (from, to) = match to {
+            Some(to) if to.time < from.time => (to, Some(from)),
+            _ => (from, to),
+        };
+        let argv = diff_args(
+            &self.host_path(&self.config.export_dir),
+            &from.commit,
+            to.map(|s| s.commit.as_str()),
+            &self.snapshot_file(terminal_id),
+            false,
+        );
+        self.pending_diff = Some((from.commit.clone(), to.map(|s| s.commit.clone())));
+        self.run_latest(CommandKind::SnapshotDiff(terminal_id), &argv);
+        self.status = Some("Comparing...".to_string());
+    }
+
+    pub(crate) fn show_diff(&mut self, terminal_id: u32, stdout: &str) {
+        let Some((from, to)) = self.pending_diff.take() else {
+            return;
+        };
+        if stdout.trim().is_empty() {
+            self.status = Some("No differences".to_string());
+            return;
+        }
+        self.status = None;
+        self.mode = UiMode::Diff(DiffView {
+            terminal_id,
+            from,
+            to,
+            text: stdout.to_string(),
+            scroll: 0,
+        });
+    }
+
+    /// Open the diff being viewed in a floating pane, where git pages it
+    pub(crate) fn open_diff_pane(&mut self, view: &DiffView) {
+        let mut argv = diff_args(
+            &self.host_path(&self.config.export_dir),
+            &view.from,
+            view.to.as_deref(),
+            &self.snapshot_file(view.terminal_id),
+            true,
+        );
+        let command = CommandToRun {
+            path: argv.remove(0).into(),
+            args: argv,
+            cwd: None,
+        };
+        open_command_pane_floating(command, None, BTreeMap::new());
+        self.status = Some(format!(
+            "Opened terminal_{} {}",
+            view.terminal_id,
+            view.range()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_lines_become_snapshots() {
+        let snapshots = Snapshot::parse_log(
+            "a1b2c3d 1760500000\n  e4f5a6b 1760496400  \n\nbroken\n0c0ffee soon\n",
+        );
+        assert_eq!(
+            snapshots,
+            vec![
+                Snapshot {
+                    commit: "a1b2c3d".to_string(),
+                    time: 1760500000
+                },
+                Snapshot {
+                    commit: "e4f5a6b".to_string(),
+                    time: 1760496400
+                },
+            ]
+        );
+        assert_eq!(snapshots[1].age(1760500000), "1h ago");
+    }
+
+    #[test]
+    fn diffs_against_the_working_file_leave_out_the_second_commit() {
+        assert_eq!(
+            diff_args("/tmp", "a1b2c3d", None, "zj-pane-1.txt", false),
+            [
+                "git",
+                "-C",
+                "/tmp",
+                "diff",
+                "--no-color",
+                "a1b2c3d",
+                "--",
+                "zj-pane-1.txt"
+            ]
+        );
+        assert_eq!(
+            diff_args("/tmp", "a", Some("b"), "f", true)[4..7],
+            ["--color", "a", "b"]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::pipe::split_args;
+use crate::{State, PANE_ROLE};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use zellij_tile::prelude::*;
+
+/// Context key carrying the name a spawned pane should get once it opens
+pub const SPAWN_NAME: &str = "pane_tracker_name";
+/// Context key carrying the directory a spawned pane was started in
+pub const SPAWN_CWD: &str = "pane_tracker_cwd";
+/// Context key carrying the id of the pane that caused the spawn
+pub const SPAWNED_BY: &str = "pane_tracker_spawned_by";
+
+/// A pane the user can launch from the tracker, written as
+/// `<name>=<command>[|cwd=<dir>][|floating]`
+#[derive(Debug, Clone)]
+pub struct PaneTemplate {
+    pub name: String,
+    pub command: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub floating: bool,
+}
+
+impl PaneTemplate {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, rest) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("'{}': expected <name>=<command>", spec))?;
+        let mut parts = rest.split('|');
+        let command = split_args(parts.next().unwrap_or_default())
+            .map_err(|e| format!("'{}': {}", spec, e))?;
+        if name.trim().is_empty() || command.is_empty() {
+            return Err(format!("'{}': expected <name>=<command>", spec));
+        }
+        let mut template = PaneTemplate {
+            name: name.trim().to_string(),
+            command,
+            cwd: None,
+            floating: false,
+        };
+        for option in parts.map(str::trim) {
+            match option.split_once('=') {
+                Some(("cwd", dir)) => template.cwd = Some(PathBuf::from(dir.trim())),
+                None if option == "floating" => template.floating = true,
+                _ => return Err(format!("'{}': unknown option '{}'", spec, option)),
+            }
+        }
+        Ok(template)
+    }
+
+    /// Parse a `;`-separated list, returning the valid templates and errors
+    pub fn parse_list(specs: &str) -> (Vec<Self>, Vec<String>) {
+        let mut templates = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            match PaneTemplate::parse(spec) {
+                Ok(template) => templates.push(template),
+                Err(e) => errors.push(e),
+            }
+        }
+        (templates, errors)
+    }
+
+    pub fn command_line(&self) -> String {
+        self.command.join(" ")
+    }
+}
+
+/// Open a command pane running `command` on behalf of the pane
+/// `spawned_by`. Once it opens it's renamed to `name` (see
+/// `State::name_spawned_pane`). Floating panes go at `coordinates` when
+/// given, wherever Zellij puts them otherwise.
+pub fn open_named_command_pane(
+    spawned_by: &str,
+    name: &str,
+    command: &[String],
+    cwd: Option<PathBuf>,
+    floating: bool,
+    coordinates: Option<FloatingPaneCoordinates>,
+) {
+    let Some((path, args)) = command.split_first() else {
+        return;
+    };
+    let mut context = BTreeMap::new();
+    context.insert(PANE_ROLE.to_string(), "spawn".to_string());
+    context.insert(SPAWN_NAME.to_string(), name.to_string());
+    context.insert(SPAWNED_BY.to_string(), spawned_by.to_string());
+    if let Some(ref cwd) = cwd {
+        context.insert(SPAWN_CWD.to_string(), cwd.display().to_string());
+    }
+    let command = CommandToRun {
+        path: path.into(),
+        args: args.to_vec(),
+        cwd,
+    };
+    if floating {
+        open_command_pane_floating(command, coordinates, context);
+    } else {
+        open_command_pane(command, context);
+    }
+}
+
+impl State {
+    /// Launch the `index`th configured pane template
+    pub(crate) fn spawn_template(&mut self, index: usize) {
+        let Some(template) = self.config.pane_templates.get(index).cloned() else {
+            return;
+        };
+        self.log.info(format!(
+            "spawning '{}': {}",
+            template.name,
+            template.command_line()
+        ));
+        let tracker = format!("plugin_{}", get_plugin_ids().plugin_id);
+        open_named_command_pane(
+            &tracker,
+            &template.name,
+            &template.command,
+            template.cwd,
+            template.floating,
+            None,
+        );
+        self.status = Some(format!("Opened {}", template.name));
+    }
+
+    /// Give a pane opened by `open_named_command_pane` its name, and
+    /// remember its cwd for re-runs and where it came from
+    pub(crate) fn name_spawned_pane(
+        &mut self,
+        terminal_id: u32,
+        context: &BTreeMap<String, String>,
+    ) {
+        if let Some(name) = context.get(SPAWN_NAME) {
+            rename_terminal_pane(terminal_id, name);
+        }
+        if let Some(parent) = context.get(SPAWNED_BY) {
+            self.spawned_by.insert(terminal_id, parent.clone());
+        }
+        if let Some(cwd) = context.get(SPAWN_CWD) {
+            self.spawned_cwd.insert(terminal_id, PathBuf::from(cwd));
+            self.refresh_git_info(terminal_id);
+        }
+    }
+
+    /// Open a pane that follows the selected pane's capture file, mirroring
+    /// its output at the capture interval
+    pub(crate) fn mirror_selected(&mut self) {
+        let Some((id, path)) = self.selected_capture() else {
+            return;
+        };
+        if !self.plain_capture(id, "Mirroring") {
+            return;
+        }
+        let name = format!(
+            "mirror: {}",
+            self.panes[&format!("terminal_{}", id)].info.title
+        );
+        let command = vec!["tail".to_string(), "-F".to_string(), path];
+        self.log
+            .info(format!("mirroring terminal_{} as '{}'", id, name));
+        let source = format!("terminal_{}", id);
+        open_named_command_pane(&source, &name, &command, None, false, None);
+        self.status = Some(format!("Mirroring terminal_{}", id));
+    }
+
+    /// Launch the selected pane's command again in a new pane with the same
+    /// name. Only panes whose command has exited can be re-run. The cwd is
+    /// only known for panes the tracker opened itself; others start in the
+    /// plugin's cwd.
+    pub(crate) fn rerun_selected(&mut self) {
+        let Some(pane_id) = self.selected_pane_id().cloned() else {
+            return;
+        };
+        let pane = &self.panes[&pane_id];
+        let Some(command_line) = pane.info.terminal_command.clone() else {
+            self.status = Some(format!("{} has no command to re-run", pane_id));
+            return;
+        };
+        if !pane.info.exited {
+            self.status = Some(format!("{} is still running", pane_id));
+            return;
+        }
+        let command = match split_args(&command_line) {
+            Ok(command) => command,
+            Err(e) => {
+                self.status = Some(format!("Can't re-run '{}': {}", command_line, e));
+                return;
+            }
+        };
+        let name = pane.info.title.clone();
+        let floating = pane.info.is_floating;
+        let cwd = self.spawned_cwd.get(&pane.info.id).cloned();
+        self.log.info(format!(
+            "re-running {} ({}): {}",
+            pane_id, name, command_line
+        ));
+        open_named_command_pane(&pane_id, &name, &command, cwd, floating, None);
+        self.status = Some(format!("Re-running {}", name));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::bench::Benchmark;
+use regex::Regex;
+use serde::Serialize;
+
+/// Running counters for the export and capture subsystems, shown in the UI
+/// and included in the names JSON
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stats {
+    /// Names JSON writes that completed
+    pub exports: u64,
+    /// Pane captures that completed
+    pub captures: u64,
+    /// Bytes written by exports and capture files
+    pub bytes_written: u64,
+    /// Host commands that failed or never reported back
+    pub commands_failed: u64,
+    /// Time from requesting the last export to the file being written
+    pub last_export_ms: Option<u64>,
+    /// Per-cycle overhead, with `benchmark` on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<Benchmark>,
+}
+
+/// Compact age of something that happened at `then`, e.g. `12m`
+pub fn format_age(then: u64, now: u64) -> String {
+    let secs = now.saturating_sub(then);
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+impl Stats {
+    /// Human-readable byte count, e.g. `12.3 KiB`
+    pub fn bytes_written_display(&self) -> String {
+        let bytes = self.bytes_written as f64;
+        if bytes < 1024.0 {
+            format!("{} B", self.bytes_written)
+        } else if bytes < 1024.0 * 1024.0 {
+            format!("{:.1} KiB", bytes / 1024.0)
+        } else {
+            format!("{:.1} MiB", bytes / (1024.0 * 1024.0))
+        }
+    }
+}
+
+/// Default `error_line_pattern`
+pub const DEFAULT_ERROR_PATTERN: &str = r"(?i)\b(error|fatal|panic(ked)?|exception|failed)\b";
+
+/// Numbers about a pane's latest capture, exported so dashboards can show
+/// output velocity without reading the content
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ContentStats {
+    pub lines: usize,
+    pub bytes: usize,
+    /// Lines that weren't in the previous capture
+    pub lines_added: usize,
+    /// Lines matching `error_line_pattern`
+    pub error_lines: usize,
+}
+
+impl ContentStats {
+    pub fn compute(previous: Option<&str>, current: &str, errors: &Regex) -> Self {
+        let lines: Vec<&str> = current.lines().collect();
+        ContentStats {
+            lines: lines.len(),
+            bytes: current.len(),
+            lines_added: previous.map_or(lines.len(), |previous| lines_added(previous, &lines)),
+            error_lines: lines.iter().filter(|line| errors.is_match(line)).count(),
+        }
+    }
+}
+
+/// Lines to compare when looking for where the previous capture ended
+const ANCHOR_LINES: usize = 3;
+
+/// How many lines of `current` come after the end of `previous`. The last
+/// few lines of the previous capture are looked up in the current one,
+/// latest occurrence first; when they're gone (the screen was cleared or
+/// scrolled past them) every line counts as new.
+fn lines_added(previous: &str, current: &[&str]) -> usize {
+    let previous: Vec<&str> = previous.lines().collect();
+    let anchor = &previous[previous.len().saturating_sub(ANCHOR_LINES)..];
+    if anchor.is_empty() {
+        return current.len();
+    }
+    current
+        .windows(anchor.len())
+        .rposition(|window| window == anchor)
+        .map_or(current.len(), |start| {
+            current.len() - (start + anchor.len())
+        })
+}
@@ -0,0 +1,224 @@
+use crate::TrackedPane;
+
+/// Pane fields a row template can reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Name,
+    Command,
+    State,
+    Category,
+    Tab,
+    Icon,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "id" => Some(Field::Id),
+            "name" => Some(Field::Name),
+            "command" | "cmd" => Some(Field::Command),
+            "state" => Some(Field::State),
+            "category" => Some(Field::Category),
+            "tab" => Some(Field::Tab),
+            "icon" => Some(Field::Icon),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Field {
+        field: Field,
+        align: Option<Align>,
+        width: Option<usize>,
+    },
+}
+
+/// Per-pane row format for the list, e.g. `{name:<20} {command} [{tab}]`
+///
+/// `{field:<N}` / `{field:>N}` pad to N columns, and every width also
+/// truncates longer values (with a trailing `…`). `{field:.N}` only truncates.
+#[derive(Debug, Clone)]
+pub struct RowTemplate {
+    parts: Vec<Part>,
+}
+
+pub const DEFAULT_ROW_TEMPLATE: &str = "{icon} {state:<8} {id} -> {name} ({command}) {category}";
+
+impl Default for RowTemplate {
+    fn default() -> Self {
+        RowTemplate::parse(DEFAULT_ROW_TEMPLATE).expect("default row template is valid")
+    }
+}
+
+impl RowTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .map(|e| start + e)
+                .ok_or_else(|| format!("unclosed '{{' in '{}'", template))?;
+            parts.push(parse_placeholder(&rest[start + 1..end])?);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(RowTemplate { parts })
+    }
+
+    pub fn render(&self, pane_id: &str, pane: &TrackedPane) -> String {
+        let mut row = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => row.push_str(text),
+                Part::Field {
+                    field,
+                    align,
+                    width,
+                } => {
+                    let value = field_value(*field, pane_id, pane);
+                    row.push_str(&fit(&value, *align, *width));
+                }
+            }
+        }
+        row.trim_end().to_string()
+    }
+}
+
+fn parse_placeholder(spec: &str) -> Result<Part, String> {
+    let (name, format) = match spec.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format.trim())),
+        None => (spec.trim(), None),
+    };
+    let field = Field::parse(name).ok_or_else(|| format!("unknown field '{{{}}}'", name))?;
+    let (align, width) = match format {
+        None | Some("") => (None, None),
+        Some(format) => {
+            let (align, digits) = match format.chars().next() {
+                Some('<') => (Some(Align::Left), &format[1..]),
+                Some('>') => (Some(Align::Right), &format[1..]),
+                Some('.') => (None, &format[1..]),
+                _ => (Some(Align::Left), format),
+            };
+            let width = digits
+                .parse::<usize>()
+                .map_err(|_| format!("invalid width in '{{{}}}'", spec))?;
+            (align, Some(width))
+        }
+    };
+    Ok(Part::Field {
+        field,
+        align,
+        width,
+    })
+}
+
+fn field_value(field: Field, pane_id: &str, pane: &TrackedPane) -> String {
+    match field {
+        Field::Id => pane_id.to_string(),
+        Field::Name => pane.info.title.clone(),
+        Field::Command => pane
+            .info
+            .terminal_command
+            .clone()
+            .unwrap_or_else(|| "-".to_string()),
+        Field::State => pane.run_state(),
+        Field::Category => pane.category.clone().unwrap_or_default(),
+        Field::Tab => (pane.tab + 1).to_string(),
+        Field::Icon => pane.icon.clone().unwrap_or_else(|| " ".to_string()),
+    }
+}
+
+/// Truncate `value` to `width` characters and pad it according to `align`
+fn fit(value: &str, align: Option<Align>, width: Option<usize>) -> String {
+    let Some(width) = width else {
+        return value.to_string();
+    };
+    let len = value.chars().count();
+    let value = if len > width {
+        let mut cut: String = value.chars().take(width.saturating_sub(1)).collect();
+        if width > 0 {
+            cut.push('…');
+        }
+        cut
+    } else {
+        value.to_string()
+    };
+    match align {
+        Some(Align::Left) => format!("{:<width$}", value, width = width),
+        Some(Align::Right) => format!("{:>width$}", value, width = width),
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zellij_tile::prelude::PaneInfo;
+
+    fn pane(title: &str, command: Option<&str>) -> TrackedPane {
+        TrackedPane {
+            tab: 1,
+            info: PaneInfo {
+                title: title.to_string(),
+                terminal_command: command.map(str::to_string),
+                exited: true,
+                exit_status: Some(2),
+                ..PaneInfo::default()
+            },
+            category: None,
+            icon: None,
+            remote_host: None,
+            container: None,
+            logical_id: String::new(),
+            floating: None,
+        }
+    }
+
+    #[test]
+    fn placeholders_are_aligned_and_truncated() {
+        let template = RowTemplate::parse("{name:<6}|{cmd:>4}|{tab}|{state:.4}").unwrap();
+        assert_eq!(
+            template.render("terminal_1", &pane("web", Some("npm run dev"))),
+            "web   |npm…|2|exi…"
+        );
+        assert_eq!(
+            template.render("terminal_1", &pane("a long title", None)),
+            "a lon…|   -|2|exi…"
+        );
+        let template = RowTemplate::parse("{ id : 3 } -> {category}").unwrap();
+        assert_eq!(template.render("terminal_1", &pane("", None)), "te… ->");
+    }
+
+    #[test]
+    fn bad_templates_are_rejected() {
+        assert_eq!(
+            RowTemplate::parse("{name").unwrap_err(),
+            "unclosed '{' in '{name'"
+        );
+        assert_eq!(
+            RowTemplate::parse("{title}").unwrap_err(),
+            "unknown field '{title}'"
+        );
+        assert_eq!(
+            RowTemplate::parse("{name:<x}").unwrap_err(),
+            "invalid width in '{name:<x}'"
+        );
+        assert!(RowTemplate::parse("no fields").is_ok());
+    }
+}
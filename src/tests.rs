@@ -0,0 +1,609 @@
+//! Replays recorded pane manifests (`tests/fixtures`) through the plugin's
+//! event loop and checks what it would write, without a running Zellij.
+//! Host commands aren't run natively; `Commands::issued` keeps them for
+//! inspection instead.
+
+use crate::bulk::BulkAction;
+use crate::commands::{CommandKind, CommandOutcome, Commands};
+use crate::config::Config;
+use crate::fixture::{self, FixtureManifest};
+use crate::hooks::HookEvent;
+use crate::ui::UiMode;
+use crate::State;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use zellij_tile::prelude::*;
+
+/// A recorded session: the manifests Zellij sent, in order
+#[derive(Deserialize)]
+struct Recording {
+    manifests: Vec<FixtureManifest>,
+}
+
+fn recording(name: &str) -> Recording {
+    let path = format!(
+        "{}/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+struct Harness {
+    state: State,
+    manifests: Vec<FixtureManifest>,
+}
+
+impl Harness {
+    /// A plugin configured from `settings` the way the plugin block would,
+    /// with the manifests of the recording `name` ready to replay
+    fn new(name: &str, settings: &[(&str, &str)]) -> Self {
+        let map: BTreeMap<String, String> = settings
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let (config, warnings) = Config::from_map(&map);
+        assert!(warnings.is_empty(), "config warnings: {:?}", warnings);
+        let mut commands = Commands::new(config.max_concurrent_commands);
+        commands.dry_run = config.dry_run;
+        let mut state = State {
+            commands,
+            config,
+            ..State::default()
+        };
+        state.active_tab = Some(0);
+        Harness {
+            state,
+            manifests: recording(name).manifests,
+        }
+    }
+
+    /// Send the `index`th recorded manifest as a `PaneUpdate`
+    fn step(&mut self, index: usize) {
+        let manifest = fixture::to_manifest(&self.manifests[index]);
+        self.state.update(Event::PaneUpdate(manifest));
+    }
+
+    /// Send the recorded manifests up to and including `index`
+    fn replay_to(&mut self, index: usize) {
+        for step in 0..=index {
+            self.step(step);
+        }
+    }
+
+    fn issued(&self, kind: &CommandKind) -> usize {
+        self.state
+            .commands
+            .issued
+            .iter()
+            .filter(|(issued, _)| issued == kind)
+            .count()
+    }
+
+    /// Contents of the latest file write of `kind`, read back from the
+    /// command line: `<shell> -c <script> sh <path> <contents>...`
+    fn written(&self, kind: &CommandKind) -> Option<String> {
+        let (_, argv) = self
+            .state
+            .commands
+            .issued
+            .iter()
+            .rev()
+            .find(|(issued, _)| issued == kind)?;
+        let script = argv.iter().position(|arg| arg == "-c")?;
+        Some(argv[script + 4..].concat())
+    }
+
+    fn written_json(&self, kind: &CommandKind) -> Value {
+        let contents = self
+            .written(kind)
+            .unwrap_or_else(|| panic!("no {} write", kind));
+        serde_json::from_str(&contents).expect("written file is JSON")
+    }
+
+    fn names(&self) -> Value {
+        self.written_json(&CommandKind::Export)
+    }
+
+    /// Feed a finished capture of a terminal pane back in
+    fn captured(&mut self, terminal_id: u32, content: &str) {
+        self.state.handle_command_outcome(CommandOutcome {
+            kind: CommandKind::Capture(terminal_id),
+            stdout: content.as_bytes().to_vec(),
+            error: None,
+        });
+    }
+}
+
+#[test]
+fn exports_every_pane_with_its_place() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.step(0);
+    let names = harness.names();
+
+    assert_eq!(names["panes"]["terminal_1"], "nvim main.rs");
+    assert_eq!(names["panes"]["plugin_0"], "zellij:tab-bar");
+    assert_eq!(names["panes"].as_object().unwrap().len(), 7);
+    assert_eq!(
+        names["tabs"]["1"],
+        serde_json::json!(["plugin_0", "terminal_1", "terminal_2", "plugin_1"])
+    );
+    assert_eq!(
+        names["tabs"]["2"],
+        serde_json::json!(["plugin_2", "terminal_3", "terminal_4"])
+    );
+    let watch = &names["pane_info"]["terminal_3"];
+    assert_eq!(watch["tab"], 2);
+    assert_eq!(watch["position"], 2);
+    assert_eq!(watch["command"], "cargo watch -x check");
+    assert_eq!(names["pane_info"]["terminal_4"]["floating"]["columns"], 80);
+    assert_eq!(names["pane_info"]["plugin_0"]["selectable"], false);
+}
+
+#[test]
+fn unchanged_manifest_is_not_written_again() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.step(0);
+    harness.step(0);
+    assert_eq!(harness.issued(&CommandKind::Export), 1);
+    harness.step(1);
+    assert_eq!(harness.issued(&CommandKind::Export), 2);
+}
+
+#[test]
+fn changes_list_added_renamed_and_removed_panes() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.replay_to(1);
+    let changes = harness.written_json(&CommandKind::Changes);
+    assert_eq!(changes["added"], serde_json::json!(["terminal_5"]));
+    assert_eq!(
+        changes["renamed"],
+        serde_json::json!([{ "id": "terminal_2", "from": "Pane #1", "to": "server" }])
+    );
+
+    harness.step(2);
+    let changes = harness.written_json(&CommandKind::Changes);
+    assert_eq!(changes["removed"], serde_json::json!(["terminal_3"]));
+    assert_eq!(harness.names()["panes"].get("terminal_3"), None);
+}
+
+#[test]
+fn hooks_skip_the_first_manifest() {
+    let mut harness = Harness::new("dev-session", &[("on_pane_open", "true")]);
+    harness.step(0);
+    assert_eq!(harness.issued(&CommandKind::Hook(HookEvent::Open)), 0);
+    harness.step(1);
+    assert_eq!(harness.issued(&CommandKind::Hook(HookEvent::Open)), 1);
+}
+
+#[test]
+fn failed_command_raises_an_alert() {
+    let mut harness = Harness::new("dev-session", &[("on_alert", "true")]);
+    harness.replay_to(2);
+    assert_eq!(harness.names()["alerts"], serde_json::json!(["terminal_5"]));
+
+    let (_, hook) = harness
+        .state
+        .commands
+        .issued
+        .iter()
+        .find(|(kind, _)| *kind == CommandKind::Hook(HookEvent::Alert))
+        .expect("on_alert ran");
+    assert!(hook.contains(&"ZJ_PANE_ID=terminal_5".to_string()));
+    assert!(hook.contains(&"ZJ_PANE_EXIT_STATUS=101".to_string()));
+    let alerts = harness
+        .written(&CommandKind::AlertLog)
+        .expect("alert logged");
+    let (time, alert) = alerts.split_once(' ').unwrap();
+    // `YYYY-MM-DDTHH:MM:SSZ`
+    assert_eq!((time.len(), &time[10..11], &time[19..]), (20, "T", "Z"));
+    assert_eq!(
+        alert,
+        "[failed] terminal_5 'tests' exited with status 101\n"
+    );
+
+    harness.state.acknowledge(5).unwrap();
+    let names = harness.names();
+    assert_eq!(names["alerts"], serde_json::json!([]));
+    assert_eq!(
+        names["acknowledged_alerts"],
+        serde_json::json!(["terminal_5"])
+    );
+}
+
+#[test]
+fn rerun_clears_the_acknowledged_alert() {
+    let mut harness = Harness::new("shared-names", &[]);
+    harness.step(0);
+    assert_eq!(harness.names()["alerts"], serde_json::json!(["terminal_3"]));
+    harness.state.acknowledge(3).unwrap();
+    harness.step(1);
+    assert!(harness.state.acknowledged.is_empty());
+    assert_eq!(
+        harness.names()["acknowledged_alerts"],
+        serde_json::json!([])
+    );
+}
+
+#[test]
+fn closed_panes_leave_nothing_behind() {
+    let mut harness = Harness::new("shared-names", &[]);
+    harness.step(0);
+    harness.captured(2, "listening on :3000\nerror: port in use\n");
+    harness
+        .state
+        .tags
+        .entry("terminal_2".to_string())
+        .or_default()
+        .insert("web".to_string());
+    harness.state.marked.insert("terminal_2".to_string());
+    assert_eq!(
+        harness
+            .state
+            .content_stats
+            .get(&2)
+            .map(|stats| stats.error_lines),
+        Some(1)
+    );
+
+    harness.step(1);
+    assert!(!harness.state.panes.contains_key("terminal_2"));
+    assert!(!harness.state.pane_content.contains_key(&2));
+    assert!(!harness.state.content_stats.contains_key(&2));
+    assert!(!harness.state.tags.contains_key("terminal_2"));
+    assert!(harness.state.marked.is_empty());
+}
+
+#[test]
+fn capture_changes_are_exported_on_the_next_tick() {
+    let mut harness = Harness::new("shared-names", &[]);
+    harness.step(0);
+    harness.captured(1, "compiled\nerror: boom\n");
+    assert_eq!(harness.issued(&CommandKind::Export), 1);
+
+    harness.state.update(Event::Timer(1.0));
+    assert_eq!(harness.issued(&CommandKind::Export), 2);
+    let names = harness.names();
+    assert_eq!(
+        names["pane_info"]["terminal_1"]["content"]["error_lines"],
+        1
+    );
+    assert!(names["pane_info"]["terminal_1"]["last_changed_at"].is_u64());
+    let changes = harness.written_json(&CommandKind::Changes);
+    assert_eq!(
+        changes["content_changed"],
+        serde_json::json!(["terminal_1"])
+    );
+
+    harness.state.update(Event::Timer(1.0));
+    assert_eq!(harness.issued(&CommandKind::Export), 2);
+}
+
+#[test]
+fn encryption_covers_every_session_file() {
+    let mut harness = Harness::new("dev-session", &[("encrypt_cmd", "age -r key")]);
+    harness.replay_to(2);
+    harness.state.acknowledge(5).unwrap();
+    for kind in [
+        CommandKind::Export,
+        CommandKind::Changes,
+        CommandKind::AlertLog,
+        CommandKind::SaveState,
+    ] {
+        let (_, argv) = harness
+            .state
+            .commands
+            .issued
+            .iter()
+            .rev()
+            .find(|(issued, _)| *issued == kind)
+            .unwrap_or_else(|| panic!("no {} write", kind));
+        let script = argv.iter().position(|arg| arg == "-c").unwrap() + 1;
+        assert!(argv[script].contains("| age -r key >"), "{} is plain", kind);
+    }
+    let alerts = harness.written(&CommandKind::AlertLog).unwrap();
+    assert!(alerts.contains("[failed] terminal_5 'tests' exited with status 101"));
+    assert_eq!(
+        harness.state.capture_reader(5),
+        Err("Captures are encrypted and decrypt_cmd isn't set".to_string())
+    );
+}
+
+/// Keys of `value` the schema node `schema` doesn't describe, and keys it
+/// requires that are missing, as JSON pointers into the export
+fn schema_drift(root: &Value, schema: &Value, value: &Value, path: &str, drift: &mut Vec<String>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let target = reference.strip_prefix('#').expect("local $ref");
+        let resolved = root.pointer(target).expect("$ref resolves");
+        return schema_drift(root, resolved, value, path, drift);
+    }
+    if let Some(branches) = schema["oneOf"].as_array().or(schema["anyOf"].as_array()) {
+        let kind = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        match branches.iter().find(|branch| branch["type"] == kind) {
+            Some(branch) => schema_drift(root, branch, value, path, drift),
+            None => drift.push(format!("{}: no {} branch", path, kind)),
+        }
+        return;
+    }
+    match value {
+        Value::Object(object) => {
+            for key in schema["required"].as_array().into_iter().flatten() {
+                let key = key.as_str().unwrap();
+                if !object.contains_key(key) {
+                    drift.push(format!("{}/{}: required but missing", path, key));
+                }
+            }
+            for (key, child) in object {
+                let child_path = format!("{}/{}", path, key);
+                match (&schema["properties"][key], &schema["additionalProperties"]) {
+                    (Value::Null, Value::Object(_)) => schema_drift(
+                        root,
+                        &schema["additionalProperties"],
+                        child,
+                        &child_path,
+                        drift,
+                    ),
+                    (Value::Null, Value::Bool(true)) => {}
+                    (Value::Null, _) => drift.push(format!("{}: not in the schema", child_path)),
+                    (property, _) => schema_drift(root, property, child, &child_path, drift),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let item_path = format!("{}/{}", path, index);
+                schema_drift(root, &schema["items"], item, &item_path, drift);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn names_export_matches_its_schema() {
+    let schema: Value = serde_json::from_str(crate::export::NAMES_SCHEMA).unwrap();
+    let mut harness = Harness::new("dev-session", &[("benchmark", "true")]);
+    harness.replay_to(1);
+    harness.captured(5, "running 3 tests\nerror: test failed\n");
+    harness
+        .state
+        .tags
+        .entry("terminal_1".to_string())
+        .or_default()
+        .insert("editor".to_string());
+    harness.state.spawned_by.insert(5, "terminal_1".to_string());
+    harness.state.git_info.insert(
+        1,
+        crate::git::GitInfo {
+            repo: "zellij-pane-tracker".to_string(),
+            branch: "main".to_string(),
+        },
+    );
+    harness.step(2);
+    harness.state.acknowledge(5).unwrap();
+
+    let names = harness.names();
+    let info = &names["pane_info"];
+    for field in ["content", "git", "floating", "spawned_by"] {
+        assert!(
+            info.as_object()
+                .unwrap()
+                .values()
+                .any(|pane| !pane[field].is_null()),
+            "fixture leaves every {} null",
+            field
+        );
+    }
+    assert!(names["stats"]["benchmark"].is_object());
+
+    let mut drift = Vec::new();
+    schema_drift(&schema, &schema, &names, "", &mut drift);
+    assert!(drift.is_empty(), "export and schema disagree: {:#?}", drift);
+}
+
+#[test]
+fn config_file_redoes_what_load_did_with_the_old_settings() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.state.apply_config_file("capture_interval \"5\"\n");
+    assert_eq!(harness.issued(&CommandKind::ReadState), 0);
+    assert!(!harness.state.hide_unselectable);
+
+    harness
+        .state
+        .apply_config_file("export_dir \"/tmp/zj\"\nhide_unselectable \"true\"\n");
+    assert!(harness.state.hide_unselectable);
+    for kind in [CommandKind::ReadRedactRules, CommandKind::ReadState] {
+        let (_, argv) = harness
+            .state
+            .commands
+            .issued
+            .iter()
+            .find(|(issued, _)| *issued == kind)
+            .unwrap_or_else(|| panic!("{} not read again", kind));
+        assert!(
+            argv.iter().any(|arg| arg.starts_with("/tmp/zj/")),
+            "{:?}",
+            argv
+        );
+    }
+    assert!(harness.written(&CommandKind::Schema).is_some());
+}
+
+#[test]
+fn bulk_close_asks_first_and_spares_plugin_panes() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.step(0);
+    harness.state.permissions = Some(PermissionStatus::Granted);
+    harness.state.onboarded = true;
+    for id in ["plugin_0", "terminal_1", "terminal_2"] {
+        harness.state.marked.insert(id.to_string());
+    }
+    harness.state.run_bulk(BulkAction::Close);
+    let UiMode::ConfirmClose(ref targets) = harness.state.mode else {
+        panic!("close didn't ask for confirmation");
+    };
+    assert_eq!(targets, &["terminal_1", "terminal_2"]);
+
+    harness
+        .state
+        .handle_key(KeyWithModifier::new(BareKey::Char('n')));
+    assert!(matches!(harness.state.mode, UiMode::Normal));
+    assert_eq!(harness.state.status.as_deref(), Some("Close cancelled"));
+    assert_eq!(harness.state.marked.len(), 3);
+
+    harness.state.run_bulk(BulkAction::Close);
+    harness
+        .state
+        .handle_key(KeyWithModifier::new(BareKey::Char('y')));
+    assert_eq!(harness.state.status.as_deref(), Some("Closed 2 panes"));
+    assert!(harness.state.marked.is_empty());
+
+    harness.state.marked.insert("plugin_0".to_string());
+    harness.state.run_bulk(BulkAction::Close);
+    assert!(matches!(harness.state.mode, UiMode::Normal));
+}
+
+#[test]
+fn bulk_group_moves_only_terminals_and_previews_under_dry_run() {
+    let mut harness = Harness::new("dev-session", &[("dry_run", "true")]);
+    harness.step(0);
+    for id in ["plugin_0", "terminal_1", "terminal_3"] {
+        harness.state.marked.insert(id.to_string());
+    }
+    harness.state.run_bulk(BulkAction::Group);
+    assert_eq!(
+        harness.state.status.as_deref(),
+        Some("Dry run: not moving 2 panes")
+    );
+
+    harness.state.marked.insert("plugin_0".to_string());
+    harness.state.run_bulk(BulkAction::Group);
+    assert_eq!(
+        harness.state.status.as_deref(),
+        Some("Only terminal panes can be moved")
+    );
+}
+
+#[test]
+fn host_commands_get_host_paths() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.state.host_cwd = Some("/home/me/zj".into());
+    assert_eq!(
+        harness.state.host_path("/host/zj-pane-1.txt"),
+        "/home/me/zj/zj-pane-1.txt"
+    );
+    assert_eq!(harness.state.host_path("/host"), "/home/me/zj");
+    assert_eq!(harness.state.host_path("/hostile/a"), "/hostile/a");
+    assert_eq!(harness.state.host_path("/tmp/a"), "/tmp/a");
+}
+
+#[test]
+fn dry_run_dumps_answer_right_away() {
+    let mut harness = Harness::new("dev-session", &[("dry_run", "true")]);
+    harness.step(0);
+    harness
+        .state
+        .start_dump("1", Some("/tmp/out.txt".to_string()), None)
+        .unwrap();
+    harness.state.handle_command_outcome(CommandOutcome {
+        kind: CommandKind::Dump(0),
+        stdout: b"hello\n".to_vec(),
+        error: None,
+    });
+    assert!(harness.state.pending_dumps.is_empty());
+    assert_eq!(harness.issued(&CommandKind::DumpWrite(0)), 0);
+}
+
+#[test]
+fn dot_graph_runs_from_session_through_tabs_to_panes() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.step(0);
+    harness.state.panes.get_mut("terminal_2").unwrap().category = Some("editor".to_string());
+    harness.state.spawned_by.insert(3, "terminal_1".to_string());
+    harness
+        .state
+        .spawned_by
+        .insert(4, "terminal_99".to_string());
+    let dot = crate::dot::render_dot(&harness.state);
+    let lines: Vec<&str> = dot.lines().map(str::trim).collect();
+    for edge in [
+        "session -> tab1;",
+        "session -> tab2;",
+        "tab1 -> \"terminal_1\";",
+        "tab2 -> \"terminal_3\";",
+        "\"terminal_1\" -> \"terminal_3\" [style=dashed, label=\"spawned\"];",
+    ] {
+        assert!(lines.contains(&edge), "missing {}", edge);
+    }
+    assert!(!dot.contains("session -> \"terminal_"));
+    assert!(!dot.contains("terminal_99"));
+    let declared = lines
+        .iter()
+        .filter(|line| line.starts_with("\"category:editor\" ["))
+        .count();
+    assert_eq!(declared, 1);
+}
+
+#[test]
+fn shared_names_are_flagged() {
+    let mut harness = Harness::new("shared-names", &[]);
+    harness.step(0);
+    let names = harness.names();
+    assert_eq!(
+        names["duplicate_names"],
+        serde_json::json!({ "server": ["terminal_1", "terminal_2"] })
+    );
+    assert_eq!(names["pane_info"]["terminal_1"]["duplicate_name"], true);
+    assert_eq!(names["pane_info"]["terminal_3"]["duplicate_name"], false);
+
+    harness.step(1);
+    assert_eq!(harness.names()["duplicate_names"], serde_json::json!({}));
+}
+
+#[test]
+fn benchmark_counts_each_cycle() {
+    let mut harness = Harness::new("dev-session", &[("benchmark", "true")]);
+    harness.replay_to(1);
+    let benchmark = harness
+        .state
+        .stats
+        .benchmark
+        .as_ref()
+        .expect("benchmark on");
+    let cycles = benchmark.get("pane_update").expect("pane updates timed");
+    assert_eq!(cycles.cycles, 2);
+    // The names JSON, the changes file and the log
+    assert!(cycles.last.commands >= 2);
+    assert!(cycles.slowest.total_us >= cycles.slowest.diff_us + cycles.slowest.serialize_us);
+
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.step(0);
+    assert!(harness.state.stats.benchmark.is_none());
+}
+
+#[test]
+fn recorded_manifest_matches_the_fixture() {
+    let mut harness = Harness::new("dev-session", &[]);
+    harness.step(1);
+    let sorted = |manifest: &FixtureManifest| {
+        let mut manifest = manifest.clone();
+        for panes in manifest.values_mut() {
+            panes.sort_by_key(|pane| (pane.is_plugin, pane.id));
+        }
+        manifest
+    };
+    assert_eq!(
+        sorted(&fixture::record(&harness.state.panes)),
+        sorted(&harness.manifests[1])
+    );
+}
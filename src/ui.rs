@@ -0,0 +1,990 @@
+use crate::actions::{self, Action};
+use crate::bulk::BulkAction;
+use crate::category::Color;
+use crate::commands::CommandKind;
+use crate::snapshots::{DiffView, Snapshot};
+use crate::stats;
+use crate::{terminal_id, unix_now, State, TrackedPane, PANE_ROLE};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use zellij_tile::prelude::*;
+
+/// What keystrokes in the tracker pane currently do
+#[derive(Default)]
+pub enum UiMode {
+    #[default]
+    Normal,
+    /// Typing a search query
+    Search(String),
+    /// Picking a pane template to launch
+    Spawn,
+    /// Typing the name to save the selected pane's tab under
+    SaveSet(String),
+    /// Picking a saved pane set to open
+    PaneSets,
+    /// Picking what to do with the marked panes
+    Bulk,
+    /// Typing a tag to add to the marked panes
+    Tag(String),
+    /// Waiting for `y` before closing these terminal panes
+    ConfirmClose(Vec<String>),
+    /// Picking a context profile to bundle
+    Profiles,
+    /// Typing a line to send to every pane in a category
+    Broadcast { group: String, line: String },
+    /// Showing the key binding overlay
+    Help,
+    /// Typing an action name in the `:` palette
+    Palette(String),
+    /// Picking two snapshots of a pane to compare
+    Snapshots {
+        terminal_id: u32,
+        snapshots: Vec<Snapshot>,
+        /// The first pick
+        from: Option<usize>,
+    },
+    /// Viewing a snapshot diff in place of the pane list
+    Diff(DiffView),
+}
+
+/// How long `key_prefix` waits for the action key
+const PREFIX_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Keys of the normal mode, listed by the `?` overlay
+const KEY_HELP: &[(&str, Action)] = &[
+    ("j, Down", Action::SelectNext),
+    ("k, Up", Action::SelectPrevious),
+    ("y", Action::Copy),
+    ("e", Action::OpenCapture),
+    ("p", Action::TogglePreview),
+    ("m", Action::Mirror),
+    ("r", Action::Rerun),
+    ("a", Action::AcceptName),
+    ("A", Action::Acknowledge),
+    ("!", Action::AlertsPane),
+    ("Space", Action::Mark),
+    ("x", Action::Bulk),
+    ("C", Action::BundleProfile),
+    ("b", Action::Broadcast),
+    ("/", Action::Search),
+    ("n", Action::NextMatch),
+    ("h", Action::ToggleHidden),
+    ("P", Action::TogglePause),
+    ("N", Action::NewPane),
+    ("S", Action::SaveSet),
+    ("O", Action::OpenSet),
+    ("d", Action::CompareSnapshots),
+    (":", Action::Palette),
+    ("?", Action::Help),
+];
+
+impl State {
+    pub(crate) fn draw(&mut self, rows: usize, _cols: usize) {
+        if let Some(sequence) = self.pending_osc52.take() {
+            print!("{}", sequence);
+        }
+        if self.paused {
+            println!("Pane Tracker - \u{1b}[1;31mPAUSED\u{1b}[0m");
+        } else {
+            println!("Pane Tracker");
+        }
+        println!("============");
+        println!("Tracking {} panes", self.panes.len());
+        println!("{}", self.tab_badges());
+        println!();
+        println!("Export: {}", self.host_path(&self.config.names_path()));
+        if self.config.dry_run {
+            println!(
+                "\u{1b}[1;33mDry run\u{1b}[0m: nothing is written, see {}",
+                self.host_path(&self.config.dry_run_path())
+            );
+        }
+        if self.config.capture_enabled() {
+            let backed_off = self.captures.backed_off();
+            let deferred = self.captures.deferred();
+            println!(
+                "Capture: every {}s ({} rules){}{}",
+                self.config.capture_interval,
+                self.config.capture_rules.len(),
+                if backed_off > 0 {
+                    format!(", {} backed off", backed_off)
+                } else {
+                    String::new()
+                },
+                if deferred > 0 {
+                    format!(", {} waiting for budget", deferred)
+                } else {
+                    String::new()
+                }
+            );
+        }
+        println!();
+        if self.needs_setup() {
+            self.draw_setup();
+            return;
+        }
+        if let UiMode::Help = self.mode {
+            self.draw_help();
+            return;
+        }
+        if let UiMode::Diff(ref view) = self.mode {
+            draw_diff(view, rows.saturating_sub(9).max(5));
+            return;
+        }
+        println!("Panes:");
+        let selected = self.selected_index();
+        let now = unix_now();
+        let duplicates = self.duplicate_names();
+        for (index, pane_id) in self.visible_pane_ids().into_iter().enumerate() {
+            let pane = &self.panes[pane_id];
+            let marker = match (index == selected, self.marked.contains(pane_id)) {
+                (true, true) => ">*",
+                (true, false) => "> ",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+            let duplicate =
+                if !pane.info.is_plugin && duplicates.contains_key(pane.info.title.as_str()) {
+                    " \u{1b}[33m[duplicate name]\u{1b}[0m"
+                } else {
+                    ""
+                };
+            let scrolled = if self.is_scrolled(pane) {
+                " [scrolled]"
+            } else {
+                ""
+            };
+            let hidden = if pane.is_interactive() {
+                ""
+            } else {
+                " [hidden]"
+            };
+            let remote = pane
+                .remote_host
+                .as_ref()
+                .map(|host| format!(" [@{}]", host))
+                .unwrap_or_default();
+            let row = format!(
+                "{} {}{}{}{}",
+                marker,
+                self.config.row_template.render(pane_id, pane),
+                remote,
+                scrolled,
+                hidden
+            );
+            let age = terminal_id(pane_id)
+                .and_then(|id| self.last_changed.get(&id))
+                .map(|at| format!(" \u{1b}[2m{}\u{1b}[0m", stats::format_age(*at, now)))
+                .unwrap_or_default();
+            let suggestion = self
+                .suggested_name(pane_id)
+                .map(|name| format!(" \u{1b}[2m(a: name it {})\u{1b}[0m", name))
+                .unwrap_or_default();
+            match self.category_color(pane) {
+                Some(color) => println!("{}{}{}{}", color.paint(&row), duplicate, age, suggestion),
+                None => println!("{}{}{}{}", row, duplicate, age, suggestion),
+            }
+        }
+        println!();
+        match self.mode {
+            UiMode::Normal => match (&self.config.key_prefix, self.prefix_pending()) {
+                (Some(prefix), true) => println!("{} ... (waiting for an action key)", prefix),
+                (Some(prefix), false) => println!(
+                    "j/k: select  {0} y: copy  {0} p: preview  {0} :: actions  ?: all keys",
+                    prefix
+                ),
+                (None, _) => {
+                    println!("j/k: select  y: copy  p: preview  /: search  :: actions  ?: all keys")
+                }
+            },
+            UiMode::Help | UiMode::Diff(_) => {}
+            UiMode::Snapshots {
+                terminal_id,
+                ref snapshots,
+                from,
+            } => {
+                println!(
+                    "Snapshots of terminal_{} (1-9: pick two, Enter: compare with now, Esc: cancel):",
+                    terminal_id
+                );
+                let now = unix_now();
+                for (index, snapshot) in snapshots.iter().enumerate() {
+                    println!(
+                        "{} {} {} {}",
+                        if from == Some(index) { "*" } else { " " },
+                        index + 1,
+                        snapshot.commit,
+                        snapshot.age(now)
+                    );
+                }
+            }
+            UiMode::Palette(ref query) => {
+                println!(":{}_  (Enter: run, Esc: cancel)", query);
+                for (index, action) in actions::palette_matches(query).iter().take(5).enumerate() {
+                    let row = format!("  {:<14} {}", action.name(), action.description());
+                    if index == 0 {
+                        println!("\u{1b}[1m{}\u{1b}[0m", row);
+                    } else {
+                        println!("\u{1b}[2m{}\u{1b}[0m", row);
+                    }
+                }
+            }
+            UiMode::Search(ref query) => {
+                println!("Search: {}_  (Enter: jump, Esc: cancel)", query);
+            }
+            UiMode::Broadcast {
+                ref group,
+                ref line,
+            } => {
+                println!(
+                    "Send to {} ({} panes): {}_  (Enter: send, Esc: cancel)",
+                    group,
+                    self.group_terminals(group).len(),
+                    line
+                );
+            }
+            UiMode::SaveSet(ref name) => {
+                println!(
+                    "Save this tab's panes as: {}_  (Enter: save, Esc: cancel)",
+                    name
+                );
+            }
+            UiMode::PaneSets => {
+                println!("Open pane set (1-9: open, Esc: cancel):");
+                for (index, (name, panes)) in self.pane_sets.iter().take(9).enumerate() {
+                    let names: Vec<&str> = panes.iter().map(|pane| pane.name.as_str()).collect();
+                    println!("  {} {:<12} {}", index + 1, name, names.join(", "));
+                }
+            }
+            UiMode::Bulk => {
+                println!(
+                    "{} panes (key: run, Esc: cancel):",
+                    self.bulk_targets().len()
+                );
+                for action in BulkAction::ALL {
+                    println!("  {} {}", action.key(), action.description());
+                }
+            }
+            UiMode::ConfirmClose(ref targets) => {
+                println!(
+                    "Close {} terminal panes? (y: close, any other key: cancel)",
+                    targets.len()
+                );
+            }
+            UiMode::Tag(ref tag) => {
+                println!(
+                    "Tag {} panes: {}_  (Enter: tag, Esc: cancel)",
+                    self.bulk_targets().len(),
+                    tag
+                );
+            }
+            UiMode::Profiles => {
+                println!("Bundle context profile (1-9: bundle, Esc: cancel):");
+                for (index, profile) in self.config.context_profiles.iter().take(9).enumerate() {
+                    let mut selection: Vec<String> = profile.tags.clone();
+                    selection.extend(profile.categories.iter().cloned());
+                    println!(
+                        "  {} {:<12} {}{}",
+                        index + 1,
+                        profile.name,
+                        if selection.is_empty() {
+                            "all panes".to_string()
+                        } else {
+                            selection.join(", ")
+                        },
+                        profile
+                            .lines
+                            .map(|lines| format!(" (last {} lines)", lines))
+                            .unwrap_or_default()
+                    );
+                }
+            }
+            UiMode::Spawn => {
+                println!("New pane (1-9: open, Esc: cancel):");
+                for (index, template) in self.config.pane_templates.iter().take(9).enumerate() {
+                    println!(
+                        "  {} {:<12} {}{}",
+                        index + 1,
+                        template.name,
+                        template.command_line(),
+                        if template.floating { " [floating]" } else { "" }
+                    );
+                }
+            }
+        }
+        if let Some(ref status) = self.status {
+            println!("{}", status);
+        }
+        if self.commands.queued() > 0 || self.commands.overdue() > 0 {
+            println!(
+                "Commands: {} running, {} queued, {} overdue",
+                self.commands.in_flight(),
+                self.commands.queued(),
+                self.commands.overdue()
+            );
+        }
+        println!();
+        println!(
+            "Stats: {} exports, {} captures, {} written{}",
+            self.stats.exports,
+            self.stats.captures,
+            self.stats.bytes_written_display(),
+            self.stats
+                .last_export_ms
+                .map(|ms| format!(", last export {}ms", ms))
+                .unwrap_or_default()
+        );
+        if let Some(cycle) = self
+            .stats
+            .benchmark
+            .as_ref()
+            .and_then(|benchmark| benchmark.get("pane_update"))
+        {
+            println!(
+                "Pane update: {}us over {} cycles (diff {}us, serialize {}us, dispatch {}us), {} commands",
+                cycle.mean.total_us,
+                cycle.cycles,
+                cycle.mean.diff_us,
+                cycle.mean.serialize_us,
+                cycle.mean.dispatch_us,
+                cycle.mean.commands
+            );
+        }
+        if self.commands.failures > 0 {
+            println!("Failed commands: {}", self.commands.failures);
+            if let Some(ref error) = self.commands.last_error {
+                println!("Last error: {}", error);
+            }
+        }
+    }
+
+    /// One badge per tab with its pane count and unacknowledged failed
+    /// panes, e.g.
+    /// `web: 4 panes ⚠1 | api: 2 panes`
+    fn tab_badges(&self) -> String {
+        let mut tabs: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+        for pane in self.panes.values().filter(|pane| pane.is_interactive()) {
+            let (count, failed) = tabs.entry(pane.tab).or_default();
+            *count += 1;
+            if pane.failed() && self.is_unacknowledged(pane.info.id) {
+                *failed += 1;
+            }
+        }
+        tabs.into_iter()
+            .map(|(tab, (count, failed))| {
+                let name = self
+                    .tab_names
+                    .get(&tab)
+                    .cloned()
+                    .unwrap_or_else(|| format!("tab {}", tab + 1));
+                let alert = if failed > 0 {
+                    format!(" \u{1b}[1;31m\u{26a0}{}\u{1b}[0m", failed)
+                } else {
+                    String::new()
+                };
+                format!("{}: {} panes{}", name, count, alert)
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    /// The `?` overlay, in place of the pane list
+    fn draw_help(&self) {
+        println!("Keys:");
+        for (key, action) in KEY_HELP {
+            if *action == Action::NewPane && self.config.pane_templates.is_empty() {
+                continue;
+            }
+            let key = match self.config.key_prefix {
+                Some(ref prefix) if !Self::unprefixed(*action) => format!("{} {}", prefix, key),
+                _ => key.to_string(),
+            };
+            println!("  {:<16} {}", key, action.description());
+        }
+        println!();
+        println!("Any key: close");
+    }
+
+    /// Handle a key press in the tracker pane. Returns whether to re-render.
+    pub(crate) fn handle_key(&mut self, key: KeyWithModifier) -> bool {
+        if self.needs_setup() {
+            return self.handle_setup_key(&key);
+        }
+        if let UiMode::Help = self.mode {
+            self.mode = UiMode::Normal;
+            return true;
+        }
+        if let UiMode::Palette(ref mut query) = self.mode {
+            match key.bare_key {
+                BareKey::Enter => {
+                    let query = std::mem::take(query);
+                    self.mode = UiMode::Normal;
+                    match actions::palette_matches(&query).first() {
+                        Some(action) => self.run_action(*action),
+                        None => self.status = Some(format!("No action matches '{}'", query)),
+                    }
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                BareKey::Backspace => {
+                    query.pop();
+                }
+                BareKey::Char(c) if !key.has_modifiers(&[KeyModifier::Ctrl, KeyModifier::Alt]) => {
+                    query.push(c);
+                }
+                _ => return false,
+            }
+            return true;
+        }
+        if let UiMode::Search(ref mut query) = self.mode {
+            match key.bare_key {
+                BareKey::Enter => {
+                    let query = std::mem::take(query);
+                    self.mode = UiMode::Normal;
+                    self.search(&query);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                BareKey::Backspace => {
+                    query.pop();
+                }
+                BareKey::Char(c) if !key.has_modifiers(&[KeyModifier::Ctrl, KeyModifier::Alt]) => {
+                    query.push(c);
+                }
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::Broadcast {
+            ref group,
+            ref mut line,
+        } = self.mode
+        {
+            match key.bare_key {
+                BareKey::Enter => {
+                    let group = group.clone();
+                    let line = std::mem::take(line);
+                    self.mode = UiMode::Normal;
+                    self.broadcast(&group, &line);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                BareKey::Backspace => {
+                    line.pop();
+                }
+                BareKey::Char(c) if !key.has_modifiers(&[KeyModifier::Ctrl, KeyModifier::Alt]) => {
+                    line.push(c);
+                }
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::Snapshots { .. } = self.mode {
+            return self.handle_snapshots_key(key);
+        }
+        if let UiMode::Diff(ref mut view) = self.mode {
+            match key.bare_key {
+                BareKey::Char('j') | BareKey::Down => view.scroll += 1,
+                BareKey::Char('k') | BareKey::Up => view.scroll = view.scroll.saturating_sub(1),
+                BareKey::Char(' ') | BareKey::PageDown => view.scroll += 10,
+                BareKey::PageUp => view.scroll = view.scroll.saturating_sub(10),
+                BareKey::Char('e') => {
+                    if let UiMode::Diff(view) = std::mem::take(&mut self.mode) {
+                        self.open_diff_pane(&view);
+                    }
+                }
+                BareKey::Esc | BareKey::Char('q') => self.mode = UiMode::Normal,
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::SaveSet(ref mut name) = self.mode {
+            match key.bare_key {
+                BareKey::Enter => {
+                    let name = std::mem::take(name);
+                    self.mode = UiMode::Normal;
+                    self.save_selected_tab_as_set(&name);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                BareKey::Backspace => {
+                    name.pop();
+                }
+                BareKey::Char(c) if !key.has_modifiers(&[KeyModifier::Ctrl, KeyModifier::Alt]) => {
+                    name.push(c);
+                }
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::PaneSets = self.mode {
+            match key.bare_key {
+                BareKey::Char(c @ '1'..='9') => {
+                    self.mode = UiMode::Normal;
+                    self.open_pane_set_at(c as usize - '1' as usize);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::Bulk = self.mode {
+            match key.bare_key {
+                BareKey::Char(c) => {
+                    let Some(action) = BulkAction::ALL.into_iter().find(|a| a.key() == c) else {
+                        return false;
+                    };
+                    self.mode = UiMode::Normal;
+                    self.run_bulk(action);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::ConfirmClose(ref mut targets) = self.mode {
+            let targets = std::mem::take(targets);
+            self.mode = UiMode::Normal;
+            if key.bare_key == BareKey::Char('y') && !key.has_modifiers(&[KeyModifier::Ctrl]) {
+                self.close_panes(&targets);
+            } else {
+                self.status = Some("Close cancelled".to_string());
+            }
+            return true;
+        }
+
+        if let UiMode::Tag(ref mut tag) = self.mode {
+            match key.bare_key {
+                BareKey::Enter => {
+                    let tag = std::mem::take(tag);
+                    self.mode = UiMode::Normal;
+                    self.tag_panes(&tag);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                BareKey::Backspace => {
+                    tag.pop();
+                }
+                BareKey::Char(c) if !key.has_modifiers(&[KeyModifier::Ctrl, KeyModifier::Alt]) => {
+                    tag.push(c);
+                }
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::Profiles = self.mode {
+            match key.bare_key {
+                BareKey::Char(c @ '1'..='9') => {
+                    self.mode = UiMode::Normal;
+                    self.bundle_profile_at(c as usize - '1' as usize);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                _ => return false,
+            }
+            return true;
+        }
+
+        if let UiMode::Spawn = self.mode {
+            match key.bare_key {
+                BareKey::Char(c @ '1'..='9') => {
+                    self.mode = UiMode::Normal;
+                    self.spawn_template(c as usize - '1' as usize);
+                }
+                BareKey::Esc => self.mode = UiMode::Normal,
+                _ => return false,
+            }
+            return true;
+        }
+
+        let prefixed = match self.config.key_prefix {
+            Some(ref prefix) if *prefix == key && !self.prefix_pending() => {
+                self.prefix_pressed = Some(Instant::now());
+                return true;
+            }
+            Some(_) => {
+                let pending = self.prefix_pending();
+                self.prefix_pressed = None;
+                pending
+            }
+            None => true,
+        };
+        if !key.has_no_modifiers() && !key.has_modifiers(&[KeyModifier::Shift]) {
+            return prefixed;
+        }
+        let action = match key.bare_key {
+            BareKey::Char('j') | BareKey::Down => Action::SelectNext,
+            BareKey::Char('k') | BareKey::Up => Action::SelectPrevious,
+            BareKey::Char('p') => Action::TogglePreview,
+            BareKey::Char('y') => Action::Copy,
+            BareKey::Char('e') => Action::OpenCapture,
+            BareKey::Char('/') => Action::Search,
+            BareKey::Char('n') => Action::NextMatch,
+            BareKey::Char('h') => Action::ToggleHidden,
+            BareKey::Char('P') => Action::TogglePause,
+            BareKey::Char('r') => Action::Rerun,
+            BareKey::Char('a') => Action::AcceptName,
+            BareKey::Char('A') => Action::Acknowledge,
+            BareKey::Char('!') => Action::AlertsPane,
+            BareKey::Char(' ') => Action::Mark,
+            BareKey::Char('x') => Action::Bulk,
+            BareKey::Char('C') => Action::BundleProfile,
+            BareKey::Char('m') => Action::Mirror,
+            BareKey::Char('b') => Action::Broadcast,
+            BareKey::Char('N') if !self.config.pane_templates.is_empty() => Action::NewPane,
+            BareKey::Char('S') => Action::SaveSet,
+            BareKey::Char('O') => Action::OpenSet,
+            BareKey::Char('d') => Action::CompareSnapshots,
+            BareKey::Char(':') => Action::Palette,
+            BareKey::Char('?') => Action::Help,
+            _ => return prefixed,
+        };
+        if !prefixed && !Self::unprefixed(action) {
+            return false;
+        }
+        self.run_action(action);
+        true
+    }
+
+    fn handle_snapshots_key(&mut self, key: KeyWithModifier) -> bool {
+        let UiMode::Snapshots {
+            terminal_id,
+            ref snapshots,
+            ref mut from,
+        } = self.mode
+        else {
+            return false;
+        };
+        let (first, second) = match key.bare_key {
+            BareKey::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                match *from {
+                    _ if index >= snapshots.len() => return false,
+                    Some(first) if first != index => {
+                        (snapshots[first].clone(), Some(snapshots[index].clone()))
+                    }
+                    _ => {
+                        *from = Some(index);
+                        return true;
+                    }
+                }
+            }
+            BareKey::Enter => match *from {
+                Some(first) => (snapshots[first].clone(), None),
+                None => return false,
+            },
+            BareKey::Esc => {
+                self.mode = UiMode::Normal;
+                return true;
+            }
+            _ => return false,
+        };
+        self.mode = UiMode::Normal;
+        self.diff_snapshots(terminal_id, &first, second.as_ref());
+        true
+    }
+
+    /// Whether `key_prefix` was pressed recently enough for the next key to
+    /// count as an action
+    fn prefix_pending(&self) -> bool {
+        self.prefix_pressed
+            .is_some_and(|at| at.elapsed() < PREFIX_TIMEOUT)
+    }
+
+    /// Actions whose keys work without `key_prefix`: moving the selection
+    /// and the help can't change anything
+    fn unprefixed(action: Action) -> bool {
+        matches!(
+            action,
+            Action::SelectNext | Action::SelectPrevious | Action::Help
+        )
+    }
+
+    pub(crate) fn toggle_preview(&mut self) {
+        self.preview_enabled = !self.preview_enabled;
+        if self.preview_enabled {
+            self.refresh_preview();
+        } else {
+            self.close_preview();
+        }
+        self.save_state();
+    }
+
+    /// Start typing a line for every pane in the selected pane's category
+    pub(crate) fn start_broadcast(&mut self) {
+        let group = self
+            .selected_pane_id()
+            .and_then(|id| self.panes[id].category.clone());
+        match group {
+            Some(group) => {
+                self.mode = UiMode::Broadcast {
+                    group,
+                    line: String::new(),
+                }
+            }
+            None => self.status = Some("Selected pane has no category to broadcast to".to_string()),
+        }
+    }
+
+    /// Running terminal panes in a category
+    fn group_terminals(&self, group: &str) -> Vec<u32> {
+        self.panes
+            .iter()
+            .filter(|(_, pane)| pane.category.as_deref() == Some(group) && !pane.info.exited)
+            .filter_map(|(id, _)| terminal_id(id))
+            .collect()
+    }
+
+    /// Type `line` followed by Enter into every running pane of `group`
+    fn broadcast(&mut self, group: &str, line: &str) {
+        let targets = self.group_terminals(group);
+        if self.commands.dry_run {
+            let what = format!("to {} ({} panes): {}", group, targets.len(), line);
+            self.commands.preview("broadcast", &what);
+            self.status = Some(format!("Dry run: not sent to {} panes", targets.len()));
+            return;
+        }
+        self.log.info(format!(
+            "broadcast to {} ({} panes): {}",
+            group,
+            targets.len(),
+            line
+        ));
+        for id in &targets {
+            write_chars_to_pane_id(&format!("{}\n", line), PaneId::Terminal(*id));
+        }
+        self.status = Some(format!("Sent to {} {} panes", targets.len(), group));
+    }
+
+    fn category_color(&self, pane: &TrackedPane) -> Option<Color> {
+        let name = pane.category.as_ref()?;
+        self.config
+            .category_rules
+            .iter()
+            .find(|rule| &rule.name == name)
+            .and_then(|rule| rule.color)
+    }
+
+    /// Pane ids shown in the list, in display order
+    fn visible_pane_ids(&self) -> Vec<&String> {
+        self.panes
+            .iter()
+            .filter(|(_, pane)| !self.hide_unselectable || pane.is_interactive())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Row of the selected pane, falling back to the first row
+    fn selected_index(&self) -> usize {
+        self.selected
+            .as_ref()
+            .and_then(|id| self.visible_pane_ids().iter().position(|k| *k == id))
+            .unwrap_or(0)
+    }
+
+    /// Pane id of the highlighted row
+    pub(crate) fn selected_pane_id(&self) -> Option<&String> {
+        self.visible_pane_ids().get(self.selected_index()).copied()
+    }
+
+    pub(crate) fn move_selection(&mut self, delta: isize) {
+        let visible = self.visible_pane_ids();
+        let current = self.selected_index();
+        let last = visible.len().saturating_sub(1);
+        let next = current.saturating_add_signed(delta).min(last);
+        if next != current || self.selected.is_none() {
+            self.selected = visible.get(next).map(|id| id.to_string());
+            self.refresh_preview();
+        }
+    }
+
+    /// Terminal id and capture path of the selected pane, if it has been
+    /// captured, as the host sees it
+    pub(crate) fn selected_capture(&mut self) -> Option<(u32, String)> {
+        let pane_id = self.selected_pane_id()?.clone();
+        match terminal_id(&pane_id).filter(|id| self.pane_content.contains_key(id)) {
+            Some(id) => Some((id, self.host_path(&self.capture_path(id)))),
+            None => {
+                self.status = Some(format!("No capture of {} yet", pane_id));
+                None
+            }
+        }
+    }
+
+    /// Whether a capture can be read straight from its file, by viewers
+    /// that can't decompress or decrypt it; sets the status if not
+    pub(crate) fn plain_capture(&mut self, terminal_id: u32, viewer: &str) -> bool {
+        match self.capture_reader(terminal_id) {
+            Ok(None) => true,
+            Ok(Some(_)) | Err(_) => {
+                self.status = Some(format!(
+                    "{} needs a plain capture file; terminal_{}'s is compressed or encrypted",
+                    viewer, terminal_id
+                ));
+                false
+            }
+        }
+    }
+
+    /// Open the selected pane's capture file in an editor pane
+    pub(crate) fn open_selected_in_editor(&mut self) {
+        let Some((id, path)) = self.selected_capture() else {
+            return;
+        };
+        if !self.plain_capture(id, "The editor") {
+            return;
+        }
+        self.log.debug(format!("opening {} in editor", path));
+        open_file(FileToOpen::new(&path), BTreeMap::new());
+        self.status = Some(format!("Opened capture of terminal_{}", id));
+    }
+
+    /// Replace the floating preview with one for the current selection
+    fn refresh_preview(&mut self) {
+        if !self.preview_enabled {
+            return;
+        }
+        self.close_preview();
+        let Some((id, path)) = self.selected_capture() else {
+            return;
+        };
+        let lines = self.config.preview_lines.to_string();
+        let mut argv = match self.capture_reader(id) {
+            Ok(None) => vec!["tail".to_string(), "-n".to_string(), lines, path],
+            Ok(Some(reader)) => self.config.shell.script(
+                &format!("{} < \"$1\" | tail -n \"$2\"", reader),
+                [path, lines],
+            ),
+            Err(e) => {
+                self.status = Some(e);
+                return;
+            }
+        };
+        let command = CommandToRun {
+            path: argv.remove(0).into(),
+            args: argv,
+            cwd: None,
+        };
+        let mut context = BTreeMap::new();
+        context.insert(PANE_ROLE.to_string(), "preview".to_string());
+        open_command_pane_floating(command, None, context);
+        self.status = Some(format!("Previewing terminal_{}", id));
+    }
+
+    fn close_preview(&mut self) {
+        if let Some(id) = self.preview_pane.take() {
+            close_terminal_pane(id);
+        }
+    }
+
+    /// Copy the selected pane's latest capture to the clipboard, through
+    /// `clipboard_cmd` when configured and OSC 52 otherwise
+    pub(crate) fn copy_selected(&mut self) {
+        let Some(pane_id) = self.selected_pane_id().cloned() else {
+            return;
+        };
+        let content = terminal_id(&pane_id).and_then(|id| Some((id, self.pane_content.get(&id)?)));
+        let Some((id, content)) = content else {
+            self.status = Some(format!("No capture of {} yet", pane_id));
+            return;
+        };
+
+        match self.config.clipboard_cmd {
+            Some(ref clipboard_cmd) => {
+                let argv = self.config.shell.pipe_to_cmd(clipboard_cmd, content);
+                self.log.debug(format!(
+                    "run [{}]: {}",
+                    CommandKind::Clipboard(id),
+                    clipboard_cmd
+                ));
+                self.commands.run(CommandKind::Clipboard(id), &argv);
+                self.status = Some(format!("Copying {}...", pane_id));
+            }
+            None => {
+                self.pending_osc52 = Some(format!("\u{1b}]52;c;{}\u{7}", BASE64.encode(content)));
+                self.status = Some(format!("Copied {} to clipboard (OSC 52)", pane_id));
+            }
+        }
+    }
+
+    /// Find the panes whose latest capture contains `query` (case-insensitive)
+    /// and focus the best match. Panes where the text appears closest to the
+    /// end of the output rank first, since that's usually the one just seen.
+    fn search(&mut self, query: &str) {
+        let needle = query.trim().to_lowercase();
+        if needle.is_empty() {
+            return;
+        }
+        let mut hits: Vec<(usize, u32)> = self
+            .pane_content
+            .iter()
+            .filter_map(|(id, content)| {
+                let lines_after = content
+                    .lines()
+                    .rev()
+                    .position(|line| line.to_lowercase().contains(&needle))?;
+                Some((lines_after, *id))
+            })
+            .collect();
+        hits.sort();
+
+        self.search_hits = hits.into_iter().map(|(_, id)| id).collect();
+        self.search_index = 0;
+        if self.search_hits.is_empty() {
+            self.status = Some(format!("No captured pane contains '{}'", query.trim()));
+            return;
+        }
+        self.focus_search_hit();
+    }
+
+    pub(crate) fn next_search_hit(&mut self) {
+        if self.search_hits.is_empty() {
+            return;
+        }
+        self.search_index = (self.search_index + 1) % self.search_hits.len();
+        self.focus_search_hit();
+    }
+
+    fn focus_search_hit(&mut self) {
+        let id = self.search_hits[self.search_index];
+        focus_terminal_pane(id, true);
+        self.selected = Some(format!("terminal_{}", id));
+        self.refresh_preview();
+        self.status = Some(format!(
+            "Match {}/{}: terminal_{} (n: next)",
+            self.search_index + 1,
+            self.search_hits.len(),
+            id
+        ));
+    }
+}
+
+/// A page of a snapshot diff, colored like `git diff`
+fn draw_diff(view: &DiffView, height: usize) {
+    println!(
+        "terminal_{} {}  (j/k: scroll, e: open in a pane, Esc: close)",
+        view.terminal_id,
+        view.range()
+    );
+    println!();
+    for line in view.text.lines().skip(view.scroll).take(height) {
+        let color = if line.starts_with("+++") || line.starts_with("---") {
+            "1"
+        } else if line.starts_with('+') {
+            "32"
+        } else if line.starts_with('-') {
+            "31"
+        } else if line.starts_with("@@") {
+            "36"
+        } else {
+            ""
+        };
+        if color.is_empty() {
+            println!("{}", line);
+        } else {
+            println!("\u{1b}[{}m{}\u{1b}[0m", color, line);
+        }
+    }
+}
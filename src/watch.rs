@@ -0,0 +1,34 @@
+use regex::Regex;
+
+/// A named pattern matched against the commands panes start, written as
+/// `<name>=<regex>`, e.g. `destructive=rm -rf|terraform (apply|destroy)`
+#[derive(Debug, Clone)]
+pub struct CommandWatcher {
+    pub name: String,
+    pub pattern: Regex,
+}
+
+impl CommandWatcher {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, pattern) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("'{}': expected <name>=<regex>", spec))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("'{}': watcher has no name", spec));
+        }
+        let pattern = Regex::new(pattern.trim()).map_err(|e| format!("'{}': {}", spec, e))?;
+        Ok(CommandWatcher {
+            name: name.to_string(),
+            pattern,
+        })
+    }
+}
+
+/// Name of the first watcher matching `command`
+pub fn matching_watcher<'a>(watchers: &'a [CommandWatcher], command: &str) -> Option<&'a str> {
+    watchers
+        .iter()
+        .find(|watcher| watcher.pattern.is_match(command))
+        .map(|watcher| watcher.name.as_str())
+}